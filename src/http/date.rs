@@ -0,0 +1,296 @@
+//! Dates in header fields such as `Date`, `Last-Modified` and `Expires`, as defined in
+//! [RFC 7231 §7.1.1.1](https://tools.ietf.org/html/rfc7231#section-7.1.1.1)
+
+use std::cmp;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Day names, starting from Monday
+const DAY_NAMES: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+/// Day names as they are written in RFC 850 dates, starting from Monday
+const LONG_DAY_NAMES: [&str; 7] = ["Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday", "Sunday"];
+/// Month names, starting from January
+const MONTH_NAMES: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+/// The last second that can be represented, which is 9999-12-31 23:59:59
+const MAX_TIMESTAMP: u64 = 253_402_300_799;
+
+/// A date and time in UTC, to the nearest second, between the start of 1970 and the end of 9999.
+///
+/// Dates are parsed from any of the three formats that are allowed by RFC 7231, but they are always formatted as an
+/// IMF-fixdate (such as `Sun, 06 Nov 1994 08:49:37 GMT`), which is the only format that may be sent. HTTP dates are
+/// always in GMT, so no time zone database is needed.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct HttpDate {
+    /// The number of seconds since the Unix epoch
+    timestamp: u64,
+}
+
+impl HttpDate {
+    /// Get the current date and time
+    pub fn now() -> HttpDate {
+        HttpDate::from(SystemTime::now())
+    }
+
+    /// Parse a date in any of the formats that are allowed by
+    /// [RFC 7231 §7.1.1.1](https://tools.ietf.org/html/rfc7231#section-7.1.1.1):
+    /// * IMF-fixdate: `Sun, 06 Nov 1994 08:49:37 GMT`
+    /// * RFC 850: `Sunday, 06-Nov-94 08:49:37 GMT`
+    /// * asctime: `Sun Nov  6 08:49:37 1994`
+    ///
+    /// The name of the day is checked, but it doesn't need to be the right one for the date. RFC 850 dates only have
+    /// two digits for the year, so they are taken to be the most recent year ending in those digits that isn't more
+    /// than 50 years in the future.
+    pub fn parse(value: &str) -> Result<HttpDate, InvalidHttpDate> {
+        HttpDate::parse_imf_fixdate(value)
+            .or_else(|| HttpDate::parse_rfc850(value, HttpDate::now().to_parts().0))
+            .or_else(|| HttpDate::parse_asctime(value))
+            .ok_or(InvalidHttpDate)
+    }
+
+    /// Parse an IMF-fixdate, such as `Sun, 06 Nov 1994 08:49:37 GMT`
+    fn parse_imf_fixdate(value: &str) -> Option<HttpDate> {
+        let s = value.as_bytes();
+        if s.len() != 29 || &s[3..5] != b", " || s[7] != b' ' || s[11] != b' ' || s[16] != b' '
+                || &s[25..] != b" GMT" {
+            return None;
+        }
+        name_index(&DAY_NAMES, &s[..3])?;
+
+        let (hour, minute, second) = parse_time(&s[17..25])?;
+        HttpDate::from_parts(parse_digits(&s[12..16])?, name_index(&MONTH_NAMES, &s[8..11])?, parse_digits(&s[5..7])?,
+                             hour, minute, second)
+    }
+
+    /// Parse an RFC 850 date, such as `Sunday, 06-Nov-94 08:49:37 GMT`, with the century chosen relative to
+    /// `current_year`
+    fn parse_rfc850(value: &str, current_year: u64) -> Option<HttpDate> {
+        let comma = value.find(", ")?;
+        name_index(&LONG_DAY_NAMES, &value.as_bytes()[..comma])?;
+        let s = &value.as_bytes()[comma + 2..];
+        if s.len() != 22 || s[2] != b'-' || s[6] != b'-' || s[9] != b' ' || &s[18..] != b" GMT" {
+            return None;
+        }
+
+        let year = full_year(parse_digits(&s[7..9])?, current_year);
+        let (hour, minute, second) = parse_time(&s[10..18])?;
+        HttpDate::from_parts(year, name_index(&MONTH_NAMES, &s[3..6])?, parse_digits(&s[..2])?, hour, minute, second)
+    }
+
+    /// Parse a date in the format of C's `asctime`, such as `Sun Nov  6 08:49:37 1994`
+    fn parse_asctime(value: &str) -> Option<HttpDate> {
+        let s = value.as_bytes();
+        if s.len() != 24 || s[3] != b' ' || s[7] != b' ' || s[10] != b' ' || s[19] != b' ' {
+            return None;
+        }
+        name_index(&DAY_NAMES, &s[..3])?;
+
+        // Days before the 10th are padded with a space rather than a zero
+        let day = if s[8] == b' ' { parse_digits(&s[9..10])? } else { parse_digits(&s[8..10])? };
+        let (hour, minute, second) = parse_time(&s[11..19])?;
+        HttpDate::from_parts(parse_digits(&s[20..])?, name_index(&MONTH_NAMES, &s[4..7])?, day, hour, minute, second)
+    }
+
+    /// Construct a date from its parts, where `month` starts from 0 and `day` starts from 1. Returns `None` if any of
+    /// them is out of range. A leap second (where `second` is 60) is treated as the first second of the next minute.
+    fn from_parts(year: u64, month: u64, day: u64, hour: u64, minute: u64, second: u64) -> Option<HttpDate> {
+        let leap_year = year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400));
+        let days_in_month = match month {
+            1 if leap_year => 29,
+            1 => 28,
+            3 | 5 | 8 | 10 => 30,
+            _ => 31,
+        };
+        if !(1970..=9999).contains(&year) || month > 11 || day < 1 || day > days_in_month || hour > 23 || minute > 59
+                || second > 60 {
+            return None;
+        }
+
+        // Convert the date to a number of days, using eras of 400 years starting on 1st March so that the leap day is at
+        // the end of the year
+        let (year, month) = if month < 2 { (year - 1, month + 10) } else { (year, month - 2) };
+        let era = year / 400;
+        let year_of_era = year % 400;
+        let day_of_year = (153 * month + 2) / 5 + day - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        let days = era * 146_097 + day_of_era - 719_468;
+
+        Some(HttpDate {
+            timestamp: cmp::min(days * 86400 + hour * 3600 + minute * 60 + second, MAX_TIMESTAMP),
+        })
+    }
+
+    /// Split the date into (year, month, day, hour, minute, second, day of the week), where the month and the day of the
+    /// week start from 0 (for January and Monday) and the day of the month starts from 1
+    fn to_parts(self) -> (u64, u64, u64, u64, u64, u64, u64) {
+        let days = self.timestamp / 86400;
+        let seconds = self.timestamp % 86400;
+
+        // This is the reverse of the conversion in `from_parts`
+        let days_since_0000_03_01 = days + 719_468;
+        let era = days_since_0000_03_01 / 146_097;
+        let day_of_era = days_since_0000_03_01 % 146_097;
+        let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let month_index = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+        let month = if month_index < 10 { month_index + 2 } else { month_index - 10 };
+        let year = era * 400 + year_of_era + if month < 2 { 1 } else { 0 };
+
+        // The Unix epoch was a Thursday
+        let weekday = (days + 3) % 7;
+
+        (year, month, day, seconds / 3600, seconds / 60 % 60, seconds % 60, weekday)
+    }
+}
+
+/// Find the index of `name` in `names`
+fn name_index(names: &[&str], name: &[u8]) -> Option<u64> {
+    names.iter().position(|n| n.as_bytes() == name).map(|i| i as u64)
+}
+
+/// Parse a decimal number, which must only contain digits
+fn parse_digits(digits: &[u8]) -> Option<u64> {
+    if digits.is_empty() || !digits.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+    Some(digits.iter().fold(0, |n, &d| n * 10 + u64::from(d - b'0')))
+}
+
+/// Expand the last two digits of a year to the most recent year ending in them that isn't more than 50 years after
+/// `current_year`, as described in [RFC 7231 §7.1.1.1](https://tools.ietf.org/html/rfc7231#section-7.1.1.1). That
+/// can be in the previous, current or next century.
+fn full_year(two_digits: u64, current_year: u64) -> u64 {
+    let mut year = current_year / 100 * 100 + 100 + two_digits;
+    while year > current_year + 50 {
+        year -= 100;
+    }
+    year
+}
+
+/// Parse a time of day in the format `08:49:37`
+fn parse_time(time: &[u8]) -> Option<(u64, u64, u64)> {
+    if time.len() != 8 || time[2] != b':' || time[5] != b':' {
+        return None;
+    }
+    Some((parse_digits(&time[..2])?, parse_digits(&time[3..5])?, parse_digits(&time[6..])?))
+}
+
+/// Dates are always formatted as an IMF-fixdate, such as `Sun, 06 Nov 1994 08:49:37 GMT`
+impl fmt::Display for HttpDate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (year, month, day, hour, minute, second, weekday) = self.to_parts();
+        write!(f, "{}, {:02} {} {:04} {:02}:{:02}:{:02} GMT", DAY_NAMES[weekday as usize], day,
+               MONTH_NAMES[month as usize], year, hour, minute, second)
+    }
+}
+
+impl FromStr for HttpDate {
+    type Err = InvalidHttpDate;
+
+    fn from_str(value: &str) -> Result<HttpDate, InvalidHttpDate> {
+        HttpDate::parse(value)
+    }
+}
+
+/// Times are rounded down to the nearest second. Times before 1970 become the start of 1970, and times after 9999
+/// become the end of 9999.
+impl From<SystemTime> for HttpDate {
+    fn from(time: SystemTime) -> HttpDate {
+        let timestamp = time.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        HttpDate {
+            timestamp: cmp::min(timestamp, MAX_TIMESTAMP),
+        }
+    }
+}
+
+impl From<HttpDate> for SystemTime {
+    fn from(date: HttpDate) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(date.timestamp)
+    }
+}
+
+/// The error returned when a date isn't in any of the formats allowed by RFC 7231, or is out of range
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct InvalidHttpDate;
+
+impl fmt::Display for InvalidHttpDate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid HTTP date")
+    }
+}
+
+impl Error for InvalidHttpDate {}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let expected = SystemTime::from(HttpDate {timestamp: 784_111_777});
+        for value in &["Sun, 06 Nov 1994 08:49:37 GMT", "Sun Nov  6 08:49:37 1994"] {
+            assert_eq!(value.parse::<HttpDate>().map(SystemTime::from), Ok(expected), "for {:?}", value);
+        }
+
+        // RFC 850 dates depend on the current year, so it is fixed here
+        let rfc850 = |value| HttpDate::parse_rfc850(value, 2024).unwrap();
+        assert_eq!(SystemTime::from(rfc850("Sunday, 06-Nov-94 08:49:37 GMT")), expected);
+        assert_eq!(rfc850("Friday, 31-Dec-99 23:59:60 GMT").to_string(), "Sat, 01 Jan 2000 00:00:00 GMT");
+        assert_eq!(rfc850("Friday, 31-Dec-38 23:59:59 GMT").to_string(), "Fri, 31 Dec 2038 23:59:59 GMT");
+        assert!(HttpDate::parse("Sunday, 06-Nov-94 08:49:37 GMT").is_ok());
+
+        assert_eq!(HttpDate::parse("Thu Feb 29 12:00:00 2024").unwrap().to_string(), "Thu, 29 Feb 2024 12:00:00 GMT");
+    }
+
+    #[test]
+    fn test_full_year() {
+        let cases = [
+            (94, 2024, 1994), (99, 2024, 1999), (0, 2024, 2000), (74, 2024, 2074), (75, 2024, 1975),
+            // Late in a century, the year can be in the next one
+            (10, 2090, 2110), (40, 2090, 2140), (41, 2090, 2041), (99, 2090, 2099),
+            (0, 2000, 2000), (50, 2000, 2050), (51, 2000, 1951),
+        ];
+        for &(two_digits, current_year, expected) in &cases {
+            assert_eq!(full_year(two_digits, current_year), expected, "for {} in {}", two_digits, current_year);
+        }
+    }
+
+    #[test]
+    fn test_invalid() {
+        let cases = [
+            "", "Sun, 06 Nov 1994 08:49:37 UTC", "sun, 06 Nov 1994 08:49:37 GMT", "Sun, 06 nov 1994 08:49:37 GMT",
+            "Sun, 6 Nov 1994 08:49:37 GMT", "Sun, 06 Nov 1994 8:49:37 GMT ", "Sun, 06 Nov 1994 24:00:00 GMT",
+            "Sun, 06 Nov 1994 08:60:00 GMT", "Sun, 31 Nov 1994 08:49:37 GMT", "Sun, 29 Feb 1900 08:49:37 GMT",
+            "Sun, 06 Nov 1969 08:49:37 GMT", "Sun, 00 Nov 1994 08:49:37 GMT", "Sun, +6 Nov 1994 08:49:37 GMT",
+            "Sun, 06-Nov-94 08:49:37 GMT", "Sunday, 06 Nov 1994 08:49:37 GMT", "Sun Nov 06 08:49:37 94",
+            "Sun Nov  6 08:49:37 1994 ",
+        ];
+        for value in &cases {
+            assert_eq!(HttpDate::parse(value), Err(InvalidHttpDate), "for {:?}", value);
+        }
+    }
+
+    #[test]
+    fn test_system_time() {
+        let time = UNIX_EPOCH + Duration::new(784_111_777, 999_999_999);
+        assert_eq!(HttpDate::from(time).to_string(), "Sun, 06 Nov 1994 08:49:37 GMT");
+        assert_eq!(SystemTime::from(HttpDate::from(time)), UNIX_EPOCH + Duration::from_secs(784_111_777));
+
+        assert_eq!(HttpDate::from(UNIX_EPOCH - Duration::from_secs(1)).to_string(), "Thu, 01 Jan 1970 00:00:00 GMT");
+        assert_eq!(HttpDate::from(UNIX_EPOCH + Duration::from_secs(u32::MAX as u64 * 100)).to_string(),
+                   "Fri, 31 Dec 9999 23:59:59 GMT");
+    }
+
+    #[test]
+    fn test_round_trip() {
+        // Every day from 1970 to 2100, at a time that changes each day
+        for days in 0..47_482u64 {
+            let date = HttpDate {timestamp: days * 86400 + days % 86400};
+            assert_eq!(HttpDate::parse(&date.to_string()), Ok(date), "for {}", date);
+        }
+    }
+}
@@ -0,0 +1,174 @@
+//! A collection of header fields, as defined in [RFC 7230 §3.2](https://tools.ietf.org/html/rfc7230#section-3.2)
+
+mod value;
+
+use std::slice;
+
+pub use self::value::HeaderValue;
+
+/// A collection of header fields, which can be used for both requests and responses.
+///
+/// Field names are compared case-insensitively, but the case that was used when they were added is preserved. Fields
+/// with the same name may appear more than once, and every value is kept in the order that it was added.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct HeaderMap {
+    /// The fields, in the order that they were added
+    fields: Vec<(String, HeaderValue)>,
+}
+
+impl HeaderMap {
+    /// Construct a new, empty, `HeaderMap`
+    pub fn new() -> HeaderMap {
+        HeaderMap {
+            fields: Vec::new(),
+        }
+    }
+
+    /// Add a field. Any existing fields with the same name are kept, and this value is stored after them.
+    pub fn add<N, V>(&mut self, name: N, value: V)
+        where N: Into<String>, V: Into<HeaderValue> {
+        self.fields.push((name.into(), value.into()));
+    }
+
+    /// Set a field, replacing any existing fields with the same name
+    pub fn set<N, V>(&mut self, name: N, value: V)
+        where N: Into<String>, V: Into<HeaderValue> {
+        let name = name.into();
+        self.remove(&name);
+        self.fields.push((name, value.into()));
+    }
+
+    /// Remove every field with the given name, returning `true` if there were any
+    pub fn remove(&mut self, name: &str) -> bool {
+        let len = self.fields.len();
+        self.fields.retain(|(n, _)| !n.eq_ignore_ascii_case(name));
+        self.fields.len() != len
+    }
+
+    /// Returns `true` if there is at least one field with the given name
+    pub fn contains(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    /// Get the value of the first field with the given name
+    pub fn get(&self, name: &str) -> Option<&HeaderValue> {
+        self.fields.iter()
+            .find(|&(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v)
+    }
+
+    /// Get the value of the first field with the given name as a string. Returns `None` if there is no such field, or
+    /// if its value isn't valid UTF-8.
+    pub fn get_str(&self, name: &str) -> Option<&str> {
+        self.get(name).and_then(HeaderValue::to_str)
+    }
+
+    /// Get the values of every field with the given name, in the order that they were added
+    pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a HeaderValue> + 'a {
+        self.fields.iter()
+            .filter(move |&(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v)
+    }
+
+    /// Get the combined value of every field with the given name, by joining them with commas. As described in
+    /// [RFC 7230 §3.2.2](https://tools.ietf.org/html/rfc7230#section-3.2.2), this is equivalent to the separate values
+    /// for fields that are defined as comma-separated lists.
+    ///
+    /// This must not be used for fields that aren't lists, such as `Set-Cookie`; use [`get_all`](#method.get_all)
+    /// for those instead.
+    pub fn get_combined(&self, name: &str) -> Option<HeaderValue> {
+        let mut values = self.get_all(name);
+        let mut combined = values.next()?.as_bytes().to_vec();
+        for value in values {
+            combined.extend_from_slice(b", ");
+            combined.extend_from_slice(value.as_bytes());
+        }
+        Some(HeaderValue::from(combined))
+    }
+
+    /// Get the number of fields, counting each repeated field separately
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    /// Returns `true` if there are no fields
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    /// Iterate over every field as `(name, value)`, in the order that they were added
+    pub fn iter(&self) -> Iter<'_> {
+        Iter {
+            inner: self.fields.iter(),
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a HeaderMap {
+    type Item = (&'a str, &'a HeaderValue);
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Iter<'a> {
+        self.iter()
+    }
+}
+
+/// An iterator over the fields in a [`HeaderMap`]
+pub struct Iter<'a> {
+    inner: slice::Iter<'a, (String, HeaderValue)>,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = (&'a str, &'a HeaderValue);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(n, v)| (n.as_str(), v))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_case_insensitive_lookup() {
+        let mut headers = HeaderMap::new();
+        headers.add("Host", "example.com");
+
+        assert_eq!(headers.get_str("host"), Some("example.com"));
+        assert_eq!(headers.get_str("HOST"), Some("example.com"));
+        assert!(headers.contains("hOsT"));
+        assert_eq!(headers.get("Hos"), None);
+    }
+
+    #[test]
+    fn test_repeated_fields() {
+        let mut headers = HeaderMap::new();
+        headers.add("Set-Cookie", "a=1");
+        headers.add("Accept", "text/html");
+        headers.add("set-cookie", "b=2");
+        headers.add("ACCEPT", "text/plain;q=0.5");
+
+        assert_eq!(headers.len(), 4);
+        assert_eq!(headers.get_str("Set-Cookie"), Some("a=1"));
+        assert_eq!(headers.get_all("Set-Cookie").collect::<Vec<_>>(), vec!["a=1", "b=2"]);
+        assert_eq!(headers.get_combined("accept"), Some(HeaderValue::from("text/html, text/plain;q=0.5")));
+        assert_eq!(headers.get_combined("Content-Length"), None);
+        assert_eq!(headers.iter().map(|(n, _)| n).collect::<Vec<_>>(),
+            vec!["Set-Cookie", "Accept", "set-cookie", "ACCEPT"]);
+    }
+
+    #[test]
+    fn test_set_and_remove() {
+        let mut headers = HeaderMap::new();
+        headers.add("Vary", "Accept");
+        headers.add("vary", "Cookie");
+        headers.set("VARY", "*");
+
+        assert_eq!(headers.get_all("Vary").collect::<Vec<_>>(), vec!["*"]);
+        assert!(headers.remove("vary"));
+        assert!(!headers.remove("vary"));
+        assert!(headers.is_empty());
+    }
+}
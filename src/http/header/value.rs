@@ -0,0 +1,144 @@
+//! The value of a header field, which is stored as the raw bytes that were received
+
+use std::borrow::Cow;
+use std::fmt;
+use std::str;
+
+/// The value of a header field.
+///
+/// [RFC 7230 §3.2.4](https://tools.ietf.org/html/rfc7230#section-3.2.4) says that bytes outside of US-ASCII should be
+/// treated as opaque data, so the value is kept exactly as it was received rather than as a `String`. This means that
+/// UTF-8 values (such as filenames in `Content-Disposition`) and values in legacy encodings (usually ISO-8859-1) are
+/// both preserved.
+#[derive(Clone, Default, Eq, PartialEq, Hash)]
+pub struct HeaderValue {
+    bytes: Vec<u8>,
+}
+
+impl HeaderValue {
+    /// Construct a new, empty, `HeaderValue`
+    pub fn new() -> HeaderValue {
+        HeaderValue {
+            bytes: Vec::new(),
+        }
+    }
+
+    /// Get the value as a string, if it is valid UTF-8 (which includes every ASCII value)
+    pub fn to_str(&self) -> Option<&str> {
+        str::from_utf8(&self.bytes).ok()
+    }
+
+    /// Get the value as a string, replacing any bytes that aren't valid UTF-8 with U+FFFD
+    pub fn to_string_lossy(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.bytes)
+    }
+
+    /// Get the raw bytes of the value
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Convert the value into its raw bytes
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+
+    /// Returns `true` if every byte of the value is in US-ASCII
+    pub fn is_ascii(&self) -> bool {
+        self.bytes.is_ascii()
+    }
+
+    /// Get the length of the value in bytes
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    /// Returns `true` if the value is empty
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+}
+
+impl From<Vec<u8>> for HeaderValue {
+    fn from(bytes: Vec<u8>) -> HeaderValue {
+        HeaderValue {
+            bytes,
+        }
+    }
+}
+
+impl<'a> From<&'a [u8]> for HeaderValue {
+    fn from(bytes: &'a [u8]) -> HeaderValue {
+        HeaderValue::from(bytes.to_vec())
+    }
+}
+
+impl From<String> for HeaderValue {
+    fn from(value: String) -> HeaderValue {
+        HeaderValue::from(value.into_bytes())
+    }
+}
+
+impl<'a> From<&'a str> for HeaderValue {
+    fn from(value: &'a str) -> HeaderValue {
+        HeaderValue::from(value.as_bytes())
+    }
+}
+
+impl PartialEq<str> for HeaderValue {
+    fn eq(&self, other: &str) -> bool {
+        self.bytes == other.as_bytes()
+    }
+}
+
+impl<'a> PartialEq<&'a str> for HeaderValue {
+    fn eq(&self, other: &&'a str) -> bool {
+        self.bytes == other.as_bytes()
+    }
+}
+
+impl PartialEq<[u8]> for HeaderValue {
+    fn eq(&self, other: &[u8]) -> bool {
+        self.bytes == other
+    }
+}
+
+/// Values are displayed as UTF-8, with any invalid bytes replaced by U+FFFD
+impl fmt::Display for HeaderValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_string_lossy())
+    }
+}
+
+/// Values are debug-printed with any bytes outside of printable ASCII escaped, so that nothing is lost
+impl fmt::Debug for HeaderValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "\"{}\"", self.bytes.escape_ascii())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_utf8_value() {
+        let value = HeaderValue::from("attachment; filename=\"résumé.pdf\"");
+
+        assert_eq!(value.to_str(), Some("attachment; filename=\"résumé.pdf\""));
+        assert!(!value.is_ascii());
+        assert_eq!(value, "attachment; filename=\"résumé.pdf\"");
+    }
+
+    #[test]
+    fn test_opaque_value() {
+        // "café" in ISO-8859-1
+        let value = HeaderValue::from(&b"caf\xe9"[..]);
+
+        assert_eq!(value.to_str(), None);
+        assert_eq!(value.as_bytes(), b"caf\xe9");
+        assert_eq!(value.to_string_lossy(), "caf\u{FFFD}");
+        assert_eq!(format!("{:?}", value), "\"caf\\xe9\"");
+    }
+}
@@ -1 +1,5 @@
-pub mod request;
\ No newline at end of file
+pub mod request;
+pub mod header;
+pub mod response;
+pub mod status;
+pub mod date;
\ No newline at end of file
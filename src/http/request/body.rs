@@ -0,0 +1,379 @@
+//! Incremental reading of request bodies, so that large bodies don't need to be held in memory
+
+use std::io::prelude::*;
+use std::io;
+use std::cmp;
+use std::fmt;
+
+use http::header::HeaderMap;
+use super::{Request, ParserConfig};
+use super::util::*;
+use super::util::TokenType::{TChar, Invalid};
+
+/// The maximum number of bytes that [`Body::get_body`] will buffer by default (1 MiB)
+pub const DEFAULT_BUFFER_LIMIT: usize = 1024 * 1024;
+
+/// How the length of a request body is determined, as described in
+/// [RFC 7230 §3.3.3](https://tools.ietf.org/html/rfc7230#section-3.3.3)
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum BodyLength {
+    /// The request has no body
+    Empty,
+    /// The body is exactly this many bytes long, as specified by the `Content-Length` header
+    Fixed(u64),
+    /// The body is sent using the chunked transfer coding, so the length isn't known in advance
+    Chunked,
+}
+
+/// The position of the reader within the body
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+enum State {
+    /// Reading a body with a known length, with this many bytes remaining
+    Fixed(u64),
+    /// Expecting a chunk size line
+    ChunkStart,
+    /// Reading the data of a chunk, with this many bytes remaining in the chunk
+    ChunkData(u64),
+    /// The whole body (including any trailers) has been read
+    Done,
+}
+
+/// The body of a request, which is read from the connection as it is consumed.
+///
+/// `Body` implements [`std::io::Read`], which yields the decoded body (without any chunked framing). Bytes that have
+/// already been buffered while parsing the headers are returned first, and the rest are read from the connection on
+/// demand.
+pub struct Body<'a> {
+    reader: StreamReader<'a, dyn Read + 'a>,
+    length: BodyLength,
+    state: State,
+    config: ParserConfig,
+    /// The number of bytes of chunk data that have been received so far, to enforce the size limit
+    chunked_size: u64,
+    trailers: HeaderMap,
+    buffer: Vec<u8>,
+    /// Where to send `100 Continue`, if the client is waiting for it before sending the body
+    continue_writer: Option<&'a mut dyn Write>,
+}
+
+impl<'a> Body<'a> {
+    /// Create a new body which will read from `reader`, with the framing given by `length`. The maximum size of the
+    /// body and its trailers is given by `config`.
+    pub fn new(mut reader: StreamReader<'a, dyn Read + 'a>, length: BodyLength, config: &ParserConfig) -> Body<'a> {
+        let state = match length {
+            // There's nothing to read for a body with `Content-Length: 0`, so it is already complete
+            BodyLength::Empty | BodyLength::Fixed(0) => State::Done,
+            BodyLength::Fixed(n) => State::Fixed(n),
+            BodyLength::Chunked => State::ChunkStart,
+        };
+        reader.get_buffer().set_body_pending(state != State::Done);
+        Body {
+            reader,
+            length,
+            state,
+            config: *config,
+            chunked_size: 0,
+            trailers: HeaderMap::new(),
+            buffer: Vec::new(),
+            continue_writer: None,
+        }
+    }
+
+    /// Send a `100 Continue` interim response to `writer` when the body is first read, for a client that sent
+    /// `Expect: 100-continue` (see [`RequestHead::expects_continue`](super::RequestHead::expects_continue)).
+    ///
+    /// The client won't send the body until it receives this, so a handler can reject the request (with 413 Payload
+    /// Too Large or 401 Unauthorized, for example) without the body ever being sent, by responding without reading
+    /// it. The connection then needs to be closed, because the client may or may not send the body afterwards.
+    pub fn expect_continue(&mut self, writer: &'a mut dyn Write) {
+        if self.state != State::Done {
+            self.continue_writer = Some(writer);
+        }
+    }
+
+    /// Returns `true` if the client is still waiting for `100 Continue`, because the body hasn't been read
+    pub fn is_awaiting_continue(&self) -> bool {
+        self.continue_writer.is_some()
+    }
+
+    /// Get the way that the length of this body is determined
+    pub fn get_length(&self) -> BodyLength {
+        self.length
+    }
+
+    /// Returns `true` once the whole body has been read
+    pub fn is_complete(&self) -> bool {
+        self.state == State::Done
+    }
+
+    /// Get the trailer fields, which can only be sent after a body that uses the chunked transfer coding. These are
+    /// only available once the whole body has been read.
+    pub fn get_trailers(&self) -> &HeaderMap {
+        &self.trailers
+    }
+
+    /// Read the rest of the body into memory, and return everything that has been buffered by this method.
+    ///
+    /// This is a convenience for handlers that don't need to stream the body. If the body is longer than
+    /// [`DEFAULT_BUFFER_LIMIT`] then [`ParseError::BodyTooLarge`] is returned. Any data that was
+    /// already consumed through [`Read`] isn't included.
+    pub fn get_body(&mut self) -> Result<&[u8], ParseError> {
+        self.get_body_with_limit(DEFAULT_BUFFER_LIMIT)
+    }
+
+    /// Read the rest of the body into memory, as [`get_body`](#method.get_body), but with a custom limit on the number
+    /// of bytes that will be buffered
+    pub fn get_body_with_limit(&mut self, limit: usize) -> Result<&[u8], ParseError> {
+        let mut chunk = [0; 4096];
+        while !self.is_complete() {
+            let n = self.read_body(&mut chunk)?;
+            if self.buffer.len() + n > limit {
+                return Err(ParseError::BodyTooLarge);
+            }
+            self.buffer.extend_from_slice(&chunk[..n]);
+        }
+        Ok(self.buffer.as_slice())
+    }
+
+    /// Read the rest of the body and throw it away. This needs to be done before the next request on the connection
+    /// can be read, if the handler didn't use the whole body.
+    pub fn discard(&mut self) -> Result<(), ParseError> {
+        let mut chunk = [0; 4096];
+        while !self.is_complete() {
+            self.read_body(&mut chunk)?;
+        }
+        Ok(())
+    }
+
+    /// Read some of the body into `buf`, returning the number of bytes read, or 0 if the body is complete
+    fn read_body(&mut self, buf: &mut [u8]) -> Result<usize, ParseError> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        if let Some(writer) = self.continue_writer.take() {
+            writer.write_all(b"HTTP/1.1 100 Continue\r\n\r\n")
+                .and_then(|_| writer.flush())
+                .map_err(ParseError::new_server_error)?;
+        }
+
+        loop {
+            match self.state {
+                State::Done => return Ok(0),
+                State::Fixed(0) => self.finish(),
+                State::Fixed(remaining) => {
+                    let n = self.read_data(buf, remaining)?;
+                    self.state = State::Fixed(remaining - n as u64);
+                    return Ok(n);
+                },
+                State::ChunkStart => {
+                    // The size line is limited like a header field, so that extensions can't go on forever
+                    let mut line = ChunkLine {reader: &mut self.reader, remaining: self.config.max_header_size};
+                    let size = parse_chunk_size(&mut line)?;
+                    parse_chunk_extensions(&mut line)?;
+
+                    // The last chunk has size 0, and is followed by the trailers instead of data
+                    if size == 0 {
+                        let trailers = &mut self.trailers;
+                        Request::parse_fields(&mut self.reader, &self.config, |name, value| {
+                            trailers.add(name, value);
+                        })?;
+                        self.finish();
+                    } else {
+                        self.chunked_size = self.chunked_size.saturating_add(size);
+                        if self.config.max_body_size.is_some_and(|max| self.chunked_size > max) {
+                            return Err(ParseError::BodyTooLarge);
+                        }
+                        self.state = State::ChunkData(size);
+                    }
+                },
+                State::ChunkData(0) => {
+                    // The chunk data must be followed by CRLF
+                    for expected in b"\r\n" {
+                        match self.reader.next() {
+                            Some(b) if b == *expected => (),
+                            Some(_) => return Err(ParseError::ChunkNotTerminated),
+                            None => return Err(ParseError::EOF),
+                        }
+                    }
+                    self.state = State::ChunkStart;
+                },
+                State::ChunkData(remaining) => {
+                    let n = self.read_data(buf, remaining)?;
+                    self.state = State::ChunkData(remaining - n as u64);
+                    return Ok(n);
+                },
+            }
+        }
+    }
+
+    /// Mark the body as complete, so that the next request on the connection can be read
+    fn finish(&mut self) {
+        self.state = State::Done;
+        self.reader.get_buffer().set_body_pending(false);
+    }
+
+    /// Read up to `remaining` bytes of data from the underlying reader into `buf`
+    fn read_data(&mut self, buf: &mut [u8], remaining: u64) -> Result<usize, ParseError> {
+        let max = cmp::min(buf.len() as u64, remaining) as usize;
+        match self.reader.read(&mut buf[..max]) {
+            Ok(0) => Err(ParseError::EOF),
+            Ok(n) => Ok(n),
+            Err(e) => Err(ParseError::new_server_error(e)),
+        }
+    }
+}
+
+impl<'a> Read for Body<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.read_body(buf).map_err(|e| match e {
+            ParseError::EOF => io::Error::new(io::ErrorKind::UnexpectedEof, e),
+            e => io::Error::new(io::ErrorKind::InvalidData, e),
+        })
+    }
+}
+
+impl<'a> fmt::Debug for Body<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Body")
+            .field("length", &self.length)
+            .field("state", &self.state)
+            .field("trailers", &self.trailers)
+            .field("awaiting_continue", &self.is_awaiting_continue())
+            .finish()
+    }
+}
+
+
+/// The line at the start of a chunk, which holds its size and extensions. This reads from the body's reader, but only
+/// up to a limited number of bytes, so that a client can't send a line that never ends.
+struct ChunkLine<'r, 'a: 'r, T: Read + ?Sized + 'a> {
+    reader: &'r mut StreamReader<'a, T>,
+    remaining: usize,
+}
+
+impl<'r, 'a: 'r, T: Read + ?Sized + 'a> ChunkLine<'r, 'a, T> {
+    /// Get the next byte of the line. Returns [`ParseError::InvalidChunkExtension`] if the line is too long, because
+    /// only the extensions (or a size with lots of leading zeros) can make it long.
+    fn next(&mut self) -> Result<u8, ParseError> {
+        if self.remaining == 0 {
+            return Err(ParseError::InvalidChunkExtension);
+        }
+        self.remaining -= 1;
+        self.reader.next().ok_or(ParseError::EOF)
+    }
+
+    /// Step back by one byte, so that the last byte is returned by `next` again
+    fn step_back(&mut self) {
+        self.reader.step_back();
+        self.remaining += 1;
+    }
+}
+
+/// Parse the size of a chunk, which is a hexadecimal number, as defined in
+/// [RFC 7230 §4.1](https://tools.ietf.org/html/rfc7230#section-4.1). The character following the size is left in
+/// `it`.
+fn parse_chunk_size<T: Read + ?Sized>(it: &mut ChunkLine<T>) -> Result<u64, ParseError> {
+    let mut size: u64 = 0;
+    let mut digits = 0;
+    loop {
+        let digit = match hex_value(it.next()?) {
+            Some(d) => d,
+            None => {
+                it.step_back();
+                break;
+            },
+        };
+        size = size.checked_mul(16)
+            .and_then(|s| s.checked_add(u64::from(digit)))
+            .ok_or(ParseError::InvalidChunkSize)?;
+        digits += 1;
+    }
+
+    if digits == 0 {
+        return Err(ParseError::InvalidChunkSize);
+    }
+    Ok(size)
+}
+
+/// Parse (and discard) the extensions at the end of a chunk size line, up to and including the CRLF. Extensions have
+/// the form `;name` or `;name=value`, where the value is either a token or a quoted string.
+fn parse_chunk_extensions<T: Read + ?Sized>(it: &mut ChunkLine<T>) -> Result<(), ParseError> {
+    loop {
+        match next_non_whitespace(it)? {
+            b'\r' => match it.next()? {
+                b'\n' => return Ok(()),
+                _ => return Err(ParseError::ChunkNotTerminated),
+            },
+            b';' => {
+                // Extension name
+                skip_whitespace(it)?;
+                if skip_token(it)? == 0 {
+                    return Err(ParseError::InvalidChunkExtension);
+                }
+
+                // Optional value
+                if next_non_whitespace(it)? != b'=' {
+                    it.step_back();
+                    continue;
+                }
+                match next_non_whitespace(it)? {
+                    b'"' => skip_quoted_string(it)?,
+                    _ => {
+                        it.step_back();
+                        if skip_token(it)? == 0 {
+                            return Err(ParseError::InvalidChunkExtension);
+                        }
+                    },
+                }
+            },
+            _ => return Err(ParseError::InvalidChunkExtension),
+        }
+    }
+}
+
+/// Consume bytes from `it` until a byte that isn't a space or tab is found, and return that byte
+fn next_non_whitespace<T: Read + ?Sized>(it: &mut ChunkLine<T>) -> Result<u8, ParseError> {
+    loop {
+        match it.next()? {
+            b' ' | b'\t' => (),
+            b => return Ok(b),
+        }
+    }
+}
+
+/// Consume any spaces and tabs from `it`, leaving the following byte to be read next
+fn skip_whitespace<T: Read + ?Sized>(it: &mut ChunkLine<T>) -> Result<(), ParseError> {
+    next_non_whitespace(it)?;
+    it.step_back();
+    Ok(())
+}
+
+/// Consume a token from `it`, leaving the following byte to be read next, and return the length of the token
+fn skip_token<T: Read + ?Sized>(it: &mut ChunkLine<T>) -> Result<usize, ParseError> {
+    let mut length = 0;
+    loop {
+        match TokenType::from(it.next()?) {
+            TChar(_) => length += 1,
+            Invalid(_) => {
+                it.step_back();
+                return Ok(length);
+            },
+        }
+    }
+}
+
+/// Consume the rest of a quoted string (after the opening quote), as defined in
+/// [RFC 7230 §3.2.6](https://tools.ietf.org/html/rfc7230#section-3.2.6)
+fn skip_quoted_string<T: Read + ?Sized>(it: &mut ChunkLine<T>) -> Result<(), ParseError> {
+    loop {
+        match it.next()? {
+            b'"' => return Ok(()),
+            b'\\' => match it.next()? {
+                b'\t' | b' '..=b'~' | 0x80..=0xFF => (),
+                _ => return Err(ParseError::InvalidChunkExtension),
+            },
+            b'\t' | b' '..=b'~' | 0x80..=0xFF => (),
+            _ => return Err(ParseError::InvalidChunkExtension),
+        }
+    }
+}
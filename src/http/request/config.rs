@@ -0,0 +1,64 @@
+//! Configuration for the request parser: limits on the size of the parts of a request, to stop clients from
+//! exhausting the server's memory, and policies for obsolete syntax that the parser may accept
+
+/// How to handle header fields that have been split across multiple lines using the obsolete line folding syntax
+/// (obs-fold), where each continuation line starts with whitespace. This is deprecated by
+/// [RFC 7230 §3.2.4](https://tools.ietf.org/html/rfc7230#section-3.2.4), but some old clients and proxies still send
+/// it.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ObsFoldPolicy {
+    /// Reject the request with 400 Bad Request
+    Reject,
+    /// Replace each fold (the line ending and the whitespace that follows it) with a single space, so the field is
+    /// treated as if it had been sent on one line
+    Replace,
+}
+
+/// How the parser handles a request. The `max_*` fields are the maximum sizes that the parser will accept for each
+/// part of a request, and exceeding any of these causes an error, which is reported to the client with the response
+/// code given below. The other fields control how leniently the parser treats obsolete syntax.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct ParserConfig {
+    /// The maximum length of the method, in bytes (501 Not Implemented, because we can't implement a method this long)
+    pub max_method_length: usize,
+    /// The maximum length of the request target, in bytes (414 URI Too Long)
+    pub max_target_length: usize,
+    /// The maximum size of a single header or trailer field, including its name, in bytes (431 Request Header Fields
+    /// Too Large)
+    pub max_header_size: usize,
+    /// The maximum number of header fields, and separately of trailer fields (431 Request Header Fields Too Large)
+    pub max_header_count: usize,
+    /// The maximum total size of the header fields, and separately of the trailer fields, in bytes (431 Request Header
+    /// Fields Too Large)
+    pub max_header_section_size: usize,
+    /// The maximum size of the body, in bytes, or `None` for no limit (413 Payload Too Large). Bodies are streamed
+    /// rather than held in memory, so there is no limit by default.
+    pub max_body_size: Option<u64>,
+    /// Whether folded header fields are accepted. They are rejected by default.
+    pub obs_fold: ObsFoldPolicy,
+}
+
+/// The name that [`ParserConfig`] had before it held anything other than limits, which is kept so that existing code
+/// still compiles
+pub type ParserLimits = ParserConfig;
+
+impl ParserConfig {
+    /// Construct the default configuration
+    pub fn new() -> ParserConfig {
+        ParserConfig {
+            max_method_length: 32,
+            max_target_length: 8 * 1024,
+            max_header_size: 8 * 1024,
+            max_header_count: 100,
+            max_header_section_size: 64 * 1024,
+            max_body_size: None,
+            obs_fold: ObsFoldPolicy::Reject,
+        }
+    }
+}
+
+impl Default for ParserConfig {
+    fn default() -> ParserConfig {
+        ParserConfig::new()
+    }
+}
@@ -0,0 +1,216 @@
+//! Reading a sequence of requests from one connection
+
+use std::io::prelude::*;
+
+use super::util::{ReadBuffer, StreamReader};
+use super::{Request, ParserConfig, ParseError};
+
+/// A connection that requests are read from, one after another.
+///
+/// Clients may pipeline requests, sending several without waiting for the responses, so reading from the stream can
+/// return the start of the next request along with the end of the current one. Those bytes are kept in the connection
+/// rather than the request, so that they are available when the next request is read. The server must respond to the
+/// requests in the order that they were received, as described in
+/// [RFC 7230 §6.3.2](https://tools.ietf.org/html/rfc7230#section-6.3.2).
+pub struct Connection<R: Read> {
+    stream: R,
+    buffer: ReadBuffer,
+    config: ParserConfig,
+    /// Where `100 Continue` interim responses are sent, if anywhere
+    continue_writer: Option<Box<dyn Write>>,
+}
+
+impl<R: Read> Connection<R> {
+    /// Create a new connection that reads requests from `stream`, using the default [`ParserConfig`]
+    pub fn new(stream: R) -> Connection<R> {
+        Connection::with_config(stream, &ParserConfig::default())
+    }
+
+    /// Create a new connection that reads requests from `stream`, rejecting any request that exceeds the limits in
+    /// `config`
+    pub fn with_config(stream: R, config: &ParserConfig) -> Connection<R> {
+        Connection {
+            stream,
+            buffer: ReadBuffer::new(),
+            config: *config,
+            continue_writer: None,
+        }
+    }
+
+    /// Send `100 Continue` to `writer` when the body of a request with `Expect: 100-continue` is first read (see
+    /// [`Body::expect_continue`](super::Body::expect_continue)). This is usually another handle to the same socket.
+    /// Without it, clients that expect `100 Continue` will wait for a while before sending the body anyway.
+    pub fn set_continue_writer<W: Write + 'static>(&mut self, writer: W) {
+        self.continue_writer = Some(Box::new(writer));
+    }
+
+    /// Read the head of the next request from the connection. The body is read through the request, and must be read
+    /// completely (or discarded with [`Body::discard`](super::Body::discard)) before the next request can be read.
+    ///
+    /// Returns `Ok(None)` if the client closed the connection cleanly between requests, and [`ParseError::EOF`] if it
+    /// was closed part of the way through a request. A [`ParseError::ServerError`] is returned if the body of the
+    /// previous request hasn't been read, because the body would otherwise be parsed as the next request.
+    pub fn next_request(&mut self) -> Result<Option<Request<'_>>, ParseError> {
+        if self.buffer.is_body_pending() {
+            return Err(ParseError::new_server_error("The body of the previous request hasn't been read"));
+        }
+
+        let stream: &mut dyn Read = &mut self.stream;
+        let head = match Request::read_head(stream, &mut self.buffer, &self.config)? {
+            Some(head) => head,
+            None => return Ok(None),
+        };
+
+        let expects_continue = head.expects_continue();
+        let mut request = head.into_request(StreamReader::with_buffer(stream, &mut self.buffer), &self.config);
+        if let (true, Some(writer)) = (expects_continue, self.continue_writer.as_mut()) {
+            request.body.expect_continue(&mut **writer);
+        }
+        Ok(Some(request))
+    }
+
+    /// Returns `true` if bytes of the next request have already been received. These are requests that the client
+    /// has pipelined.
+    pub fn has_buffered(&self) -> bool {
+        !self.buffer.is_empty()
+    }
+
+    /// Get a reference to the underlying stream
+    pub fn get_ref(&self) -> &R {
+        &self.stream
+    }
+
+    /// Get a mutable reference to the underlying stream. Reading from it directly will skip any bytes that have
+    /// already been buffered.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.stream
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::cmp;
+    use std::io;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_pipelined_requests() {
+        let stream: &[u8] = concat!(
+            "POST /a HTTP/1.1\r\nHost: example.com\r\nContent-Length: 5\r\n\r\nhello",
+            "POST /b HTTP/1.1\r\nHost: example.com\r\nTransfer-Encoding: chunked\r\n\r\n",
+            "5\r\nworld\r\n0\r\n\r\n",
+            "GET /c HTTP/1.1\r\nHost: example.com\r\n\r\n",
+        ).as_bytes();
+
+        // Whether the requests arrive all at once or a few bytes at a time, they are all read in order
+        for &chunk_size in &[1, 7, stream.len()] {
+            let mut connection = Connection::new(ChunkedReader { data: stream, chunk_size });
+            let mut bodies = Vec::new();
+            while let Some(mut request) = connection.next_request().unwrap() {
+                let body = request.get_body().unwrap().to_vec();
+                bodies.push((request.get_path().to_string(), body));
+            }
+            assert_eq!(bodies, vec![
+                ("/a".to_string(), b"hello".to_vec()),
+                ("/b".to_string(), b"world".to_vec()),
+                ("/c".to_string(), Vec::new()),
+            ]);
+        }
+    }
+
+    #[test]
+    fn test_unread_body() {
+        let stream: &[u8] = concat!(
+            "POST /a HTTP/1.1\r\nHost: example.com\r\nContent-Length: 24\r\n\r\n",
+            "GET /smuggled HTTP/1.1\r\n",
+            "GET /b HTTP/1.1\r\nHost: example.com\r\n\r\n",
+        ).as_bytes();
+
+        let mut connection = Connection::new(stream);
+        connection.next_request().unwrap().unwrap();
+        match connection.next_request() {
+            Err(ParseError::ServerError(_)) => (),
+            r => panic!("Expected a server error, got {:?}", r.map(|r| r.map(|r| r.get_path().to_string()))),
+        }
+
+        // Once the body has been discarded, the next request can be read
+        let mut connection = Connection::new(stream);
+        connection.next_request().unwrap().unwrap().get_body_reader().discard().unwrap();
+        assert!(connection.has_buffered());
+        assert_eq!(connection.next_request().unwrap().unwrap().get_path(), "/b");
+        assert!(connection.next_request().unwrap().is_none());
+
+        // An empty body doesn't need to be read
+        let stream: &[u8] = concat!(
+            "POST /a HTTP/1.1\r\nHost: a\r\nContent-Length: 0\r\n\r\n",
+            "GET /b HTTP/1.1\r\nHost: a\r\n\r\n",
+        ).as_bytes();
+        let mut connection = Connection::new(stream);
+        assert!(connection.next_request().unwrap().unwrap().get_body_reader().is_complete());
+        assert_eq!(connection.next_request().unwrap().unwrap().get_path(), "/b");
+    }
+
+    #[test]
+    fn test_truncated_request() {
+        let mut connection = Connection::new(&b"GET /a HTTP/1.1\r\nHost: a\r\n\r\nGET /b HTTP/1.1\r\n"[..]);
+        assert_eq!(connection.next_request().unwrap().unwrap().get_path(), "/a");
+        match connection.next_request() {
+            Err(ParseError::EOF) => (),
+            r => panic!("Expected EOF, got {:?}", r.map(|r| r.map(|r| r.get_path().to_string()))),
+        }
+    }
+
+    #[test]
+    fn test_expect_continue() {
+        let stream: &[u8] = concat!(
+            "PUT /a HTTP/1.1\r\nHost: example.com\r\nExpect: 100-continue\r\nContent-Length: 5\r\n\r\nhello",
+            "PUT /b HTTP/1.0\r\nExpect: 100-continue\r\nContent-Length: 5\r\n\r\nworld",
+        ).as_bytes();
+        let written = Rc::new(RefCell::new(Vec::new()));
+        let mut connection = Connection::new(stream);
+        connection.set_continue_writer(SharedWriter(written.clone()));
+
+        // Nothing is sent until the body is read, so the handler can still reject the request
+        let mut request = connection.next_request().unwrap().unwrap();
+        assert!(request.is_awaiting_continue());
+        assert!(written.borrow().is_empty());
+        assert_eq!(request.get_body().unwrap(), b"hello");
+        assert!(!request.is_awaiting_continue());
+        assert_eq!(&written.borrow()[..], b"HTTP/1.1 100 Continue\r\n\r\n");
+
+        // HTTP/1.0 clients don't understand interim responses
+        let mut request = connection.next_request().unwrap().unwrap();
+        assert!(!request.is_awaiting_continue());
+        assert_eq!(request.get_body().unwrap(), b"world");
+        assert_eq!(written.borrow().len(), 25);
+    }
+
+    /// A writer that appends to a buffer that the test can still read
+    struct SharedWriter(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// A reader that returns at most `chunk_size` bytes from each call to `read`, like a socket
+    struct ChunkedReader<'a> {
+        data: &'a [u8],
+        chunk_size: usize,
+    }
+
+    impl<'a> Read for ChunkedReader<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = cmp::min(self.chunk_size, buf.len());
+            (&mut self.data).take(n as u64).read(buf)
+        }
+    }
+}
@@ -0,0 +1,138 @@
+//! Validation of the headers that determine how the request body is framed.
+//!
+//! If two servers on the path of a request (such as a load balancer and this server) disagree about where the body
+//! ends, then a client can hide a second request inside the body of the first, which only one of them will see. This
+//! is known as request smuggling. To prevent it, any request whose framing could be interpreted in more than one way
+//! is rejected, following [RFC 7230 §3.3.3](https://tools.ietf.org/html/rfc7230#section-3.3.3).
+
+use std::str;
+
+use http::header::{HeaderMap, HeaderValue};
+use super::Request;
+use super::body::BodyLength;
+use super::util::ParseError;
+
+/// Determine how the body of a request with the given version and headers is framed, or return an error if the
+/// framing is ambiguous. The rules are applied in the order given in RFC 7230 §3.3.3 (omitting those that only apply
+/// to responses):
+///
+/// * If `Transfer-Encoding` is present, `chunked` must be the final coding, and it must not be applied more than
+///   once. Otherwise there is no way to determine the length, so the request is rejected.
+/// * If both `Transfer-Encoding` and `Content-Length` are present, `Transfer-Encoding` would take precedence, but the
+///   RFC notes that this "ought to be handled as an error", so it is rejected.
+/// * If `Content-Length` appears more than once (or as a list), all of the values must be identical, and the body has
+///   that length. Differing or invalid values are rejected.
+/// * Otherwise, the request has no body.
+///
+/// HTTP/1.0 doesn't define `Transfer-Encoding`, so an HTTP/1.0 intermediary would ignore it; it is therefore rejected
+/// in HTTP/1.0 requests.
+pub fn body_length(version: (u8, u8), headers: &HeaderMap) -> Result<BodyLength, ParseError> {
+    let content_length = headers.get_combined("Content-Length");
+    let transfer_encoding = headers.get_combined("Transfer-Encoding");
+
+    body_length_from(version, content_length.as_ref().map(HeaderValue::as_bytes),
+        transfer_encoding.as_ref().map(HeaderValue::as_bytes))
+}
+
+/// Determine how the body is framed from the combined values of the `Content-Length` and `Transfer-Encoding` fields,
+/// using the rules described in [`body_length`]. This allows the framing to be checked without building a
+/// [`HeaderMap`].
+pub fn body_length_from(version: (u8, u8), content_length: Option<&[u8]>, transfer_encoding: Option<&[u8]>)
+    -> Result<BodyLength, ParseError> {
+    if let Some(value) = transfer_encoding {
+        if content_length.is_some() {
+            return Err(ParseError::ConflictingFraming);
+        }
+        if version < (1, 1) {
+            return Err(ParseError::InvalidTransferEncoding);
+        }
+
+        let value = str::from_utf8(value).map_err(|_| ParseError::InvalidTransferEncoding)?;
+        // Empty list elements are permitted by the list syntax (RFC 7230 §7), but must be ignored
+        let codings: Vec<&str> = value.split(',').map(trim_whitespace).filter(|c| !c.is_empty()).collect();
+        let (last, others) = match codings.split_last() {
+            Some(split) => split,
+            None => return Err(ParseError::InvalidTransferEncoding),
+        };
+        if !last.eq_ignore_ascii_case("chunked") || others.iter().any(|c| c.eq_ignore_ascii_case("chunked")) {
+            return Err(ParseError::InvalidTransferEncoding);
+        }
+        if !others.is_empty() {
+            return Err(ParseError::UnsupportedTransferCoding);
+        }
+        return Ok(BodyLength::Chunked);
+    }
+
+    match content_length {
+        Some(value) => {
+            let value = str::from_utf8(value).map_err(|_| ParseError::InvalidContentLength)?;
+            let mut length = None;
+            for element in value.split(',') {
+                let n = Request::parse_content_length(trim_whitespace(element))?;
+                if length.is_some_and(|l| l != n) {
+                    return Err(ParseError::ConflictingFraming);
+                }
+                length = Some(n);
+            }
+            // Splitting always produces at least one element
+            Ok(BodyLength::Fixed(length.unwrap()))
+        },
+        None => Ok(BodyLength::Empty),
+    }
+}
+
+/// Remove optional whitespace (spaces and tabs) from both ends of a list element
+fn trim_whitespace(s: &str) -> &str {
+    s.trim_matches(|c| c == ' ' || c == '\t')
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Fields<'a> = &'a [(&'a str, &'a str)];
+
+    fn headers(fields: Fields) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for &(name, value) in fields {
+            headers.add(name, value);
+        }
+        headers
+    }
+
+    #[test]
+    fn test_valid_framing() {
+        let cases: &[(Fields, BodyLength)] = &[
+            (&[], BodyLength::Empty),
+            (&[("Content-Length", "0")], BodyLength::Fixed(0)),
+            (&[("Content-Length", "005")], BodyLength::Fixed(5)),
+            (&[("Content-Length", "5"), ("content-length", "5")], BodyLength::Fixed(5)),
+            (&[("Content-Length", "5, 5,5")], BodyLength::Fixed(5)),
+            (&[("Transfer-Encoding", "chunked")], BodyLength::Chunked),
+            (&[("Transfer-Encoding", "CHUNKED")], BodyLength::Chunked),
+            (&[("Transfer-Encoding", ", chunked ,")], BodyLength::Chunked),
+        ];
+        for &(fields, expected) in cases {
+            assert_eq!(body_length((1, 1), &headers(fields)).unwrap(), expected, "for {:?}", fields);
+        }
+    }
+
+    #[test]
+    fn test_invalid_framing() {
+        let cases: &[((u8, u8), Fields, &str)] = &[
+            ((1, 1), &[("Content-Length", "1"), ("Transfer-Encoding", "chunked")], "ConflictingFraming"),
+            ((1, 1), &[("Content-Length", "5"), ("Content-Length", "6")], "ConflictingFraming"),
+            ((1, 1), &[("Content-Length", "5,")], "InvalidContentLength"),
+            ((1, 1), &[("Transfer-Encoding", "chunked, gzip")], "InvalidTransferEncoding"),
+            ((1, 1), &[("Transfer-Encoding", "chunked, chunked")], "InvalidTransferEncoding"),
+            ((1, 1), &[("Transfer-Encoding", "")], "InvalidTransferEncoding"),
+            ((1, 0), &[("Transfer-Encoding", "chunked")], "InvalidTransferEncoding"),
+            ((1, 1), &[("Transfer-Encoding", "gzip, chunked")], "UnsupportedTransferCoding"),
+        ];
+        for &(version, fields, expected) in cases {
+            let result = body_length(version, &headers(fields));
+            assert_eq!(format!("{:?}", result), format!("Err({})", expected), "for {:?}", fields);
+        }
+    }
+}
@@ -0,0 +1,130 @@
+//! The head of a request, which is everything before the body
+
+use std::io::prelude::*;
+
+use http::header::HeaderMap;
+
+use super::util::StreamReader;
+use super::{Request, Method, RequestTarget, Authority, Query, Body, BodyLength, ParserConfig, ParseError};
+
+/// The request line and headers of a request, once they have been parsed and validated. This is everything that is
+/// needed to decide how to handle the request, but the body hasn't been read yet.
+#[derive(Debug)]
+pub struct RequestHead {
+    /// HTTP Version
+    version: (u8, u8),
+    /// HTTP Method (verb)
+    method: Method,
+    /// Target (usually the URI from path onwards)
+    target: RequestTarget,
+    /// The HTTP request headers
+    headers: HeaderMap,
+    /// The host that the request was sent to, from the target or the `Host` header
+    host: Option<Authority>,
+    /// The way that the length of the body is determined
+    body_length: BodyLength,
+}
+
+impl RequestHead {
+    /// Construct a new `RequestHead` from its parts, which must already have been validated
+    pub fn new(version: (u8, u8), method: Method, target: RequestTarget, headers: HeaderMap, host: Option<Authority>,
+               body_length: BodyLength) -> RequestHead {
+        RequestHead {
+            version, method, target, headers, host, body_length,
+        }
+    }
+
+    /// Get the request's HTTP version, in the format (major, minor)
+    pub fn get_version(&self) -> (u8, u8) {
+        self.version
+    }
+    /// Get the HTTP version that should be used for the response, which is 1.0 for HTTP/1.0 requests and 1.1 otherwise
+    pub fn get_response_version(&self) -> (u8, u8) {
+        if self.version < (1, 1) {
+            (1, 0)
+        } else {
+            (1, 1)
+        }
+    }
+    /// Returns `true` if the connection can be used for further requests after this one, as described in
+    /// [RFC 7230 §6.3](https://tools.ietf.org/html/rfc7230#section-6.3). HTTP/1.1 connections are persistent unless
+    /// the client sends `Connection: close`, but HTTP/1.0 connections are only persistent if the client sends
+    /// `Connection: keep-alive`.
+    pub fn is_persistent(&self) -> bool {
+        if self.has_connection_option("close") {
+            false
+        } else if self.version >= (1, 1) {
+            true
+        } else {
+            self.has_connection_option("keep-alive")
+        }
+    }
+    /// Returns `true` if the client is waiting for a `100 Continue` interim response before it sends the body, as
+    /// described in [RFC 7231 §5.1.1](https://tools.ietf.org/html/rfc7231#section-5.1.1). The expectation is ignored
+    /// for HTTP/1.0 requests, because HTTP/1.0 clients don't understand interim responses.
+    pub fn expects_continue(&self) -> bool {
+        let has_body = self.body_length != BodyLength::Empty && self.body_length != BodyLength::Fixed(0);
+        has_body && self.version >= (1, 1) && self.headers.get_all("Expect")
+            .filter_map(|value| value.to_str())
+            .any(|value| value.split(',').any(|e| e.trim().eq_ignore_ascii_case("100-continue")))
+    }
+    /// Returns `true` if the response can use the chunked transfer coding, which HTTP/1.0 clients don't understand
+    pub fn allows_chunked_response(&self) -> bool {
+        self.version >= (1, 1)
+    }
+    /// Returns `true` if the `Connection` header contains `option`, ignoring case
+    fn has_connection_option(&self, option: &str) -> bool {
+        match self.headers.get_combined("Connection") {
+            Some(value) => value.to_str()
+                .is_some_and(|v| v.split(',').any(|o| o.trim().eq_ignore_ascii_case(option))),
+            None => false,
+        }
+    }
+    /// Get the request method
+    pub fn get_method(&self) -> &Method {
+        &self.method
+    }
+    /// Get the request target (usually the [origin form](https://tools.ietf.org/html/rfc7230#section-5.3.1) of the
+    /// request url, which is the absolute path followed optionally by the query)
+    pub fn get_target(&self) -> &RequestTarget {
+        &self.target
+    }
+    /// Get the requested path, after percent-decoding and removing dot segments. See [`RequestTarget::get_path`].
+    pub fn get_path(&self) -> &str {
+        self.target.get_path()
+    }
+    /// Get the requested path exactly as it was sent
+    pub fn get_raw_path(&self) -> &str {
+        self.target.get_raw_path()
+    }
+    /// Parse the query into its parameters. The query is only parsed when this is called, so an error here (which
+    /// should result in 400 Bad Request) doesn't prevent handlers that don't use the query from working.
+    pub fn get_query(&self) -> Result<Query, ParseError> {
+        match self.target.get_query() {
+            Some(query) => Query::parse(query),
+            None => Ok(Query::default()),
+        }
+    }
+    /// Get the request headers
+    pub fn get_headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+    /// Get the host (and port) that the request was sent to. This is taken from the target if it is in absolute form,
+    /// or from the `Host` header otherwise, and is `None` if neither was supplied.
+    pub fn get_host(&self) -> Option<&Authority> {
+        self.host.as_ref()
+    }
+    /// Get the way that the length of the body is determined
+    pub fn get_body_length(&self) -> BodyLength {
+        self.body_length
+    }
+
+    /// Convert this into a full request, which will read its body from `reader`, subject to `config`
+    pub fn into_request<'a>(self, reader: StreamReader<'a, dyn Read + 'a>, config: &ParserConfig) -> Request<'a> {
+        let body = Body::new(reader, self.body_length, config);
+        Request {
+            head: self,
+            body,
+        }
+    }
+}
@@ -1,300 +1,288 @@
 //! [RFC 7230](https://tools.ietf.org/html/rfc723) compliant HTTP 1.1 request parser
 
 mod util;
+mod body;
+mod target;
+mod query;
+mod config;
+mod framing;
+mod head;
+mod parser;
+mod request_ref;
+mod connection;
 
 use std::io::prelude::*;
-use std::net::TcpStream;
-use std::collections::HashMap;
+use std::str;
 use std::sync::Arc;
 
+use http::header::{HeaderMap, HeaderValue};
+
 use self::util::*;
 pub use self::util::ParseError;
-use self::util::TokenType::{TChar, Invalid};
+pub use self::body::{Body, BodyLength, DEFAULT_BUFFER_LIMIT};
+pub use self::target::{RequestTarget, TargetForm, Authority, Host};
+pub use self::query::Query;
+pub use self::config::{ParserConfig, ParserLimits, ObsFoldPolicy};
+pub use self::head::RequestHead;
+pub use self::parser::{Parser, Status};
+pub use self::request_ref::RequestRef;
+pub use self::connection::Connection;
+pub(crate) use self::util::{TCHAR, FIELD_CHAR};
+use self::parser::FieldParser;
 
 /// A container for the details of an HTTP request
-#[derive(Debug, Eq, PartialEq)]
-pub struct Request {
-    /// HTTP Version
-    version: (u8, u8),
-    /// HTTP Method (verb)
-    method: Method,
-    /// Target (the URI from path onwards)
-    target: String,
-    /// The HTTP request headers
-    headers: HashMap<String, String>,
-    /// The request body
-    body: Vec<u8>,
+#[derive(Debug)]
+pub struct Request<'a> {
+    /// The request line and headers
+    head: RequestHead,
+    /// The request body, which is read from the connection on demand
+    body: Body<'a>,
 }
 
-impl Request {
+impl<'a> Request<'a> {
+    /// Get the head of the request, which is everything except the body
+    pub fn get_head(&self) -> &RequestHead {
+        &self.head
+    }
     /// Get the request's HTTP version, in the format (major, minor)
     pub fn get_version(&self) -> (u8, u8) {
-        self.version
+        self.head.get_version()
+    }
+    /// Get the HTTP version that should be used for the response. See [`RequestHead::get_response_version`].
+    pub fn get_response_version(&self) -> (u8, u8) {
+        self.head.get_response_version()
+    }
+    /// Returns `true` if the connection can be used for further requests after this one. See
+    /// [`RequestHead::is_persistent`].
+    pub fn is_persistent(&self) -> bool {
+        self.head.is_persistent()
+    }
+    /// Returns `true` if the response can use the chunked transfer coding, which HTTP/1.0 clients don't understand
+    pub fn allows_chunked_response(&self) -> bool {
+        self.head.allows_chunked_response()
     }
     /// Get the request method
     pub fn get_method(&self) -> &Method {
-        &self.method
+        self.head.get_method()
     }
-    /// Get the request target (usually the [origin form](https://tools.ietf.org/html/rfc7230#section-5.3.1) of the
-    /// request url, which is the absolute path followed optionally by the query)
-    pub fn get_target(&self) -> &str {
-        &self.target
+    /// Get the request target. See [`RequestHead::get_target`].
+    pub fn get_target(&self) -> &RequestTarget {
+        self.head.get_target()
+    }
+    /// Get the requested path, after percent-decoding and removing dot segments. See [`RequestTarget::get_path`].
+    pub fn get_path(&self) -> &str {
+        self.head.get_path()
+    }
+    /// Get the requested path exactly as it was sent
+    pub fn get_raw_path(&self) -> &str {
+        self.head.get_raw_path()
+    }
+    /// Parse the query into its parameters. See [`RequestHead::get_query`].
+    pub fn get_query(&self) -> Result<Query, ParseError> {
+        self.head.get_query()
     }
     /// Get the request headers
-    /// TODO: This should either be a collection or parsed to combine comma separated headers
-    pub fn get_headers(&self) -> &HashMap<String, String> {
-        &self.headers
+    pub fn get_headers(&self) -> &HeaderMap {
+        self.head.get_headers()
+    }
+    /// Get the host (and port) that the request was sent to. See [`RequestHead::get_host`].
+    pub fn get_host(&self) -> Option<&Authority> {
+        self.head.get_host()
     }
-    /// Get the request body, if one was supplied in the request
-    pub fn get_body(&self) -> &[u8] {
-        self.body.as_slice()
+    /// Get the request body, if one was supplied in the request, reading it into memory first if necessary. See
+    /// [`Body::get_body`] for details.
+    pub fn get_body(&mut self) -> Result<&[u8], ParseError> {
+        self.body.get_body()
+    }
+    /// Get the request body as a reader, so it can be consumed incrementally
+    pub fn get_body_reader(&mut self) -> &mut Body<'a> {
+        &mut self.body
+    }
+    /// Get the trailer fields, which can only be sent after a body that uses the chunked transfer coding. These are
+    /// only available once the body has been read.
+    pub fn get_trailers(&self) -> &HeaderMap {
+        self.body.get_trailers()
+    }
+    /// Returns `true` if the client is waiting for `100 Continue` before it sends the body. See
+    /// [`Body::expect_continue`].
+    pub fn is_awaiting_continue(&self) -> bool {
+        self.body.is_awaiting_continue()
     }
 }
 
-impl Request {
-    /// Parse a request stream
-    pub fn from(stream: &mut TcpStream) -> Result<Request, ParseError> {
-        let mut builder = RequestBuilder::new();
-        let mut it = StreamReader::from(stream);
-
-        Request::parse_request_line(&mut builder, &mut it)?;
-        Request::parse_headers(&mut builder, &mut it)?;
-
-        // Sanity checks
-        
-
-        Request::parse_body(&mut builder, &mut it)?;
-
-        Ok(builder.into_request().unwrap())
+impl<'a> Request<'a> {
+    /// Parse the head of a request from a stream, using the default [`ParserConfig`]. The body isn't read until it
+    /// is requested, so the stream is borrowed for as long as the request is alive.
+    ///
+    /// The stream can be anything that implements [`Read`], such as a `TcpStream`, a Unix socket, a TLS stream, an
+    /// in-memory buffer or a file containing a captured request.
+    pub fn from<R>(stream: &'a mut R) -> Result<Request<'a>, ParseError> where R: Read + 'a {
+        Request::from_with_config(stream, &ParserConfig::default())
     }
 
-    /// Parse the request line, which is the first line of the request
-    /// 
-    /// It should have the form `Method Target HTTP/Version`, as defined in
-    /// [RFC 7230 §3.1.1](https://tools.ietf.org/html/rfc7230#section-3.1.1).
-    fn parse_request_line<T>(builder: &mut RequestBuilder, it: &mut StreamReader<T>) -> Result<(), ParseError>
-        where T: Read {
-        // Request method
-        let method = Request::parse_request_method(it)?;
-        builder.set_method(method);
-
-        // Target
-        let target = Request::parse_request_target(it)?;
-        builder.set_target(target);
-
-        // Version
-        let version = Request::parse_request_version(it)?;
-        builder.set_version(version.0, version.1);
-
-        Ok(())
+    /// Parse the head of a request from a stream, rejecting any request that exceeds the limits in `config`. This
+    /// reads from the stream until the [`Parser`] has seen the whole head, and any bytes that were read after it are
+    /// kept for the body.
+    pub fn from_with_config<R>(stream: &'a mut R, config: &ParserConfig) -> Result<Request<'a>, ParseError>
+        where R: Read + 'a {
+        let stream: &'a mut (dyn Read + 'a) = stream;
+        let mut buffer = ReadBuffer::new();
+
+        match Request::read_head(stream, &mut buffer, config)? {
+            Some(head) => Ok(head.into_request(StreamReader::from_buffer(stream, buffer), config)),
+            None => Err(ParseError::EOF),
+        }
     }
 
-    /// Parse the method (GET, POST, etc). It should be 1 or more visible characters, treated case-sensitively, and it
-    /// is followed by a single space (according to
-    /// [RFC 7230 §3.1.1](https://tools.ietf.org/html/rfc7230#section-3.1.1)).
-    fn parse_request_method<T>(it: &mut StreamReader<T>) -> Result<Method, ParseError>
-        where T: Read {
-        let mut method = Vec::new();
-        // Read bytes
-        for b in it {
-            match TokenType::from(b) {
-                TChar(c) => method.push(c),
-                Invalid(b' ') => return Ok(Method::from(method)),
-                Invalid(_) => return Err(ParseError::IllegalCharacter),
+    /// Read the head of a request from `buffer`, and then from `stream` once everything in the buffer has been used.
+    /// Any bytes after the head are left in the buffer.
+    ///
+    /// Returns `Ok(None)` if the stream ends (or fails) before the first byte of the request, so that callers can tell
+    /// a connection that was closed between requests apart from one that was closed part of the way through one.
+    fn read_head<T: Read + ?Sized>(stream: &mut T, buffer: &mut ReadBuffer, config: &ParserConfig)
+            -> Result<Option<RequestHead>, ParseError> {
+        let mut parser = Parser::with_config(config);
+        let mut started = false;
+
+        loop {
+            if !buffer.is_empty() {
+                started = true;
+                let available = buffer.available().len();
+                match parser.parse(buffer.available())? {
+                    Status::Complete(head, consumed) => {
+                        buffer.consume(consumed);
+                        return Ok(Some(head));
+                    },
+                    Status::NeedMore => buffer.consume(available),
+                }
             }
-        }
 
-        Err(ParseError::EOF)
+            match buffer.fill(stream) {
+                Ok(0) | Err(_) if started => return Err(ParseError::EOF),
+                Ok(0) | Err(_) => return Ok(None),
+                Ok(_) => (),
+            }
+        }
     }
 
-    /// Parse the target (requested resource). The most general form is 1 or more visible characters (followed by a
-    /// single space), though more restrictive parsing would be permitted as defined in
-    /// [RFC 7230 §5.3](https://tools.ietf.org/html/rfc7230#section-5.3).
-    fn parse_request_target<T>(it: &mut StreamReader<T>) -> Result<String, ParseError>
-        where T: Read {
-        let mut target = Vec::new();
-        // Read bytes
+    /// Parse a block of header fields followed by an empty line from `it`, passing each field to `add_field` as it is
+    /// parsed. This is used for the trailers of a chunked body, which have the same syntax as the headers.
+    fn parse_fields<T, F>(it: &mut StreamReader<T>, config: &ParserConfig, mut add_field: F) -> Result<(), ParseError>
+        where T: Read + ?Sized, F: FnMut(String, HeaderValue) {
+        let mut fields = FieldParser::new();
         for b in it {
-            match b {
-                // Allowed characters in URLs per [RFC 3986](https://tools.ietf.org/html/rfc3986#appendix-A)
-                b'!' | b'#'...b';' | b'=' | b'?'...b'[' | b']'...b'z' | b'|' | b'~' => target.push(b),
-                b' ' => return Ok(String::from_utf8(target).unwrap()), // Safe to unwrap because input is sanitised
-                _ => return Err(ParseError::IllegalCharacter),
+            if fields.push(b, config, &mut add_field)? {
+                return Ok(());
             }
         }
 
         Err(ParseError::EOF)
     }
 
-    /// Parse the HTTP version, which should be HTTP/maj.min, where maj and min are single digits, as defined in
-    /// [RFC 7230 §2.6](https://tools.ietf.org/html/rfc7230#section-2.6).
-    fn parse_request_version<T>(it: &mut StreamReader<T>) -> Result<(u8, u8), ParseError>
-        where T: Read {
+    /// Check the `Host` header, as required by [RFC 7230 §5.4](https://tools.ietf.org/html/rfc7230#section-5.4), and
+    /// determine which host the request was sent to.
+    ///
+    /// HTTP/1.1 requests must have exactly one `Host` header, which must be a valid authority (or empty if the target
+    /// doesn't have one). If the target is in absolute form then its authority takes precedence, so the `Host` header
+    /// is replaced to match it.
+    fn validate_host(builder: &mut RequestBuilder) -> Result<(), ParseError> {
+        let target_authority = match builder.target {
+            Some(ref target) if target.get_form() == TargetForm::Absolute => target.get_authority().cloned(),
+            _ => None,
+        };
+        let from_target = target_authority.is_some();
+        let version = builder.version.unwrap_or((1, 1));
+        let host = Request::find_host(version, builder.get_headers().get_all("Host").map(HeaderValue::as_bytes),
+                                      target_authority)?;
 
-        let expected_it = "HTTP/".bytes();
+        if let (true, Some(ref authority)) = (from_target, &host) {
+            builder.headers.set("Host", authority.to_string());
+        }
+        builder.set_host(host);
 
-        for expected in expected_it {
-            match it.next() {
-                Some(b) if b == expected => (),
-                Some(_) => return Err(ParseError::IllegalCharacter),
-                None => return Err(ParseError::EOF),
-            }
+        Ok(())
+    }
+
+    /// Find the host that a request was sent to, given the values of its `Host` headers and the authority of its
+    /// target if it is in absolute form. This does the checks for [`validate_host`](#method.validate_host), and is
+    /// shared with [`RequestRef`] so that both parsers accept the same requests.
+    fn find_host<I, V>(version: (u8, u8), mut values: I, target_authority: Option<Authority>)
+            -> Result<Option<Authority>, ParseError>
+        where I: Iterator<Item = V>, V: AsRef<[u8]> {
+        let value = values.next();
+        if values.next().is_some() {
+            return Err(ParseError::new_bad_request("Multiple Host headers"));
         }
-        let major = match it.next() {
-            Some(n) if n >= 48 && n <= 57 => n - 48,
-            Some(_) => return Err(ParseError::IllegalCharacter),
-            None => return Err(ParseError::EOF),
-        };
-        match it.next() {
-            Some(b'.') => (),
-            Some(_) => return Err(ParseError::IllegalCharacter),
-            None => return Err(ParseError::EOF),
+        if value.is_none() && version >= (1, 1) {
+            return Err(ParseError::MissingRequiredHeader("Host"));
         }
-        let minor = match it.next() {
-            Some(n) if n >= 48 && n <= 57 => n - 48,
-            Some(_) => return Err(ParseError::IllegalCharacter),
-            None => return Err(ParseError::EOF),
-        };
-        
-        // Should now be at the end of the Request Line
-        match it.next() {
-            Some(b'\r') => (),
-            Some(_) => return Err(ParseError::IllegalCharacter),
-            None => return Err(ParseError::EOF),
+
+        if target_authority.is_some() {
+            return Ok(target_authority);
         }
-        match it.next() {
-            Some(b'\n') => (),
-            Some(_) => return Err(ParseError::IllegalCharacter),
-            None => return Err(ParseError::EOF),
+        match value {
+            None => Ok(None),
+            Some(ref value) if value.as_ref().is_empty() => Ok(None),
+            Some(ref value) => match str::from_utf8(value.as_ref()).map(Authority::parse) {
+                Ok(Ok(authority)) => Ok(Some(authority)),
+                _ => Err(ParseError::new_bad_request("Invalid Host header")),
+            },
         }
+    }
+
+    /// Check the `Expect` header, as described in [RFC 7231 §5.1.1](https://tools.ietf.org/html/rfc7231#section-5.1.1).
+    /// `100-continue` is the only expectation that has been defined, so a request with any other expectation is
+    /// rejected with 417 Expectation Failed. HTTP/1.0 requests are never rejected, because the specification says
+    /// that the header must be ignored in them.
+    fn validate_expect(builder: &RequestBuilder) -> Result<(), ParseError> {
+        let value = builder.get_headers().get_combined("Expect");
+        Request::check_expect(builder.version.unwrap_or((1, 1)), value.as_ref().map(HeaderValue::as_bytes))
+    }
 
-        Ok((major, minor))
-    }
-
-    /// Parse the request headers from `it` into `builder`, as specified in
-    /// [RFC 7230 §3.2](https://tools.ietf.org/html/rfc7230#section-3.2)
-    fn parse_headers<T: Read>(builder: &mut RequestBuilder, it: &mut StreamReader<T>) -> Result<(), ParseError> {
-        // An enum to store the current state of the parser
-        enum ParserState {
-            // After a new line, ready to parse the header name
-            Start,
-            // Currently parsing the header name
-            Name {name: Vec<u8>},
-            // Currently parsing the whitespace after the : but before the value
-            ValueLeadingWS {name: String},
-            // Currently parsing the value
-            Value {name: String, value: Vec<u8>},
-            // Currently parsing the new line (CR (here) LF)
-            NewLine,
-            // Currently parsing the final new line (CR LF CR (here) LF)
-            FinalNewLine,
+    /// Check the combined value of the `Expect` headers, if there are any, for
+    /// [`validate_expect`](#method.validate_expect). This is shared with [`RequestRef`].
+    fn check_expect(version: (u8, u8), value: Option<&[u8]>) -> Result<(), ParseError> {
+        let value = match value {
+            Some(value) if version >= (1, 1) => value,
+            _ => return Ok(()),
         };
-        let mut state = ParserState::Start;
+        let valid = str::from_utf8(value).is_ok_and(|v| v.split(',')
+            .map(str::trim)
+            .all(|e| e.is_empty() || e.eq_ignore_ascii_case("100-continue")));
+        if valid {
+            Ok(())
+        } else {
+            Err(ParseError::ExpectationFailed)
+        }
+    }
 
-        'outer: loop {
-            let b = match it.next() {
-                None => return Err(ParseError::EOF),
-                Some(b) => b,
-            };
+    /// Determine the length of the request body, if there is one. This is given by the `Transfer-Encoding` and
+    /// `Content-Length` headers, which are validated by [`framing::body_length`] to reject any request where the
+    /// length is ambiguous. The body itself is read on demand by [`Body`].
+    fn parse_body(builder: &mut RequestBuilder, config: &ParserConfig) -> Result<(), ParseError> {
+        let version = builder.version.unwrap_or((1, 1));
+        let length = framing::body_length(version, builder.get_headers())?;
 
-            // Wrap this in a loop so that we can cheaply transition to a different state without having consumed
-            // any characters
-            loop {
-                match state {
-                    ParserState::Start => match b {
-                        b'\r' => state = ParserState::FinalNewLine,
-                        _ => {
-                            // Move straight into Name without consuming this character
-                            state = ParserState::Name {
-                                name: Vec::new()
-                            };
-                            continue;
-                        }
-                    },
-                    ParserState::Name {name: mut n} => match TokenType::from(b) {
-                        TChar(c) => {
-                            n.push(c);
-                            state = ParserState::Name {name: n}
-                        },
-                        Invalid(b':') => {
-                            // Safe to convert to UTF-8 because it was constructed from just ASCII characters
-                            let name = String::from_utf8(n).unwrap();
-                            state = ParserState::ValueLeadingWS {name: name};
-                        },
-                        Invalid(_) => return Err(ParseError::IllegalCharacter),
-                    },
-                    ParserState::ValueLeadingWS {name: n} => match b {
-                        b' ' | b'\t' => state = ParserState::ValueLeadingWS {name: n},
-                        _ => {
-                            // Move straight into Value without consuming
-                            state = ParserState::Value {
-                                name: n,
-                                value: Vec::new()
-                            };
-                            continue;
-                        }
-                    },
-                    ParserState::Value {name: n, value: mut v} => match b {
-                        b'\t' | b' '...b'~' => {
-                            v.push(b);
-                            state = ParserState::Value {name: n, value: v};
-                        },
-                        0x80...0xFF => {
-                            // The specification says that headers containing these characters SHOULD be considered as
-                            // opaque data. However, doing that means we can't treat the headers as strings, because
-                            // this would break UTF-8 compliance, thereby vastly increasing the complexity of the rest
-                            // of the code. The non-ASCII characters will therefore be silently discarded
-                            state = ParserState::Value {name: n, value: v};
-                        }
-                        b'\r' => {
-                            // Because we discarded the invalid characters, it's safe to convert to UTF-8
-                            let value = String::from_utf8(v).unwrap();
-                            // Store the header
-                            builder.add_header(n, value);
-                            // Transition to expect the LF
-                            state = ParserState::NewLine;
-                        },
-                        _ => return Err(ParseError::IllegalCharacter),
-                    },
-                    ParserState::NewLine => match b {
-                        b'\n' => state = ParserState::Start,
-                        _ => return Err(ParseError::IllegalCharacter),
-                    },
-                    ParserState::FinalNewLine => match b {
-                        b'\n' => break 'outer,
-                        _ => return Err(ParseError::IllegalCharacter),
-                    }
-                }
-
-                // Consume the next character
-                break;
+        if let BodyLength::Fixed(n) = length {
+            if config.max_body_size.is_some_and(|max| n > max) {
+                return Err(ParseError::BodyTooLarge);
             }
         }
+        builder.set_body_length(length);
 
         Ok(())
     }
 
-    fn parse_body<T: Read>(builder: &mut RequestBuilder, it: &mut StreamReader<T>) -> Result<(), ParseError> {
-        println!("Starting parse body");
-        // TODO: can't read to end
-        Ok(())/*
-        match it.get_inner().read_to_end(builder.get_body()) {
-            Ok(0) => Ok(()),
-            Ok(_) => {
-                println!("Body read complete");
-                Ok(())
-            },
-            Err(e) => Err(ParseError::new_server_error(e)),
-        }*/
+    /// Parse the value of a `Content-Length` header, which must be a non-negative decimal integer
+    /// ([RFC 7230 §3.3.2](https://tools.ietf.org/html/rfc7230#section-3.3.2)).
+    fn parse_content_length(value: &str) -> Result<u64, ParseError> {
+        if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(ParseError::InvalidContentLength);
+        }
+        value.parse().map_err(|_| ParseError::InvalidContentLength)
     }
 }
 
-unsafe impl Send for Request {}
-
-// nb. Not syncable until fully constructed (when the hashmap becomes effectively immutable)
-// Public interface is completely syncable
-unsafe impl Sync for Request {}
-
 
 
 /// HTTP Methods (verbs), as defined by [RFC 7231 §4](https://tools.ietf.org/html/rfc7231#section-4)
@@ -327,7 +315,7 @@ impl Method {
         if name.as_slice() == &b"CONNECT"[..] { return Connect };
         if name.as_slice() == &b"OPTIONS"[..] { return Options };
         if name.as_slice() == &b"TRACE"[..] { return Trace };
-        return Custom(Arc::from(name));
+        Custom(Arc::from(name))
     }
 }
 
@@ -339,9 +327,10 @@ unsafe impl Send for Method {}
 struct RequestBuilder {
     version: Option<(u8, u8)>,
     method: Option<Method>,
-    target: Option<String>,
-    headers: HashMap<String, String>,
-    body: Vec<u8>,
+    target: Option<RequestTarget>,
+    headers: HeaderMap,
+    host: Option<Authority>,
+    body_length: BodyLength,
 }
 
 impl RequestBuilder {
@@ -351,8 +340,9 @@ impl RequestBuilder {
             version: None,
             method: None,
             target: None,
-            headers: HashMap::new(),
-            body: Vec::new(),
+            headers: HeaderMap::new(),
+            host: None,
+            body_length: BodyLength::Empty,
         }
     }
 
@@ -367,36 +357,41 @@ impl RequestBuilder {
     }
 
     /// Set the request target
-    pub fn set_target(&mut self, target: String) {
+    pub fn set_target(&mut self, target: RequestTarget) {
         self.target = Some(target);
     }
 
-    /// Set the body of the request
-    pub fn get_body(&mut self) -> &mut Vec<u8> {
-        &mut self.body
+    /// Set the host that the request was sent to
+    pub fn set_host(&mut self, host: Option<Authority>) {
+        self.host = host;
+    }
+
+    /// Set the way that the length of the body is determined
+    pub fn set_body_length(&mut self, length: BodyLength) {
+        self.body_length = length;
     }
 
-    /// Add a header. This method currently stores the latest version in the event of duplicate headers.
-    pub fn add_header(&mut self, key: String, val: String) {
-        self.headers.insert(key, val);
+    /// Add a header. If there are already headers with the same name, this value is stored after them.
+    pub fn add_header(&mut self, key: String, val: HeaderValue) {
+        self.headers.add(key, val);
     }
 
-    pub fn get_headers(&self) -> &HashMap<String, String> {
+    /// Get the headers that have been added so far
+    pub fn get_headers(&self) -> &HeaderMap {
         &self.headers
     }
 
-    /// Convert this request builder into a full request
-    pub fn into_request(self) -> Option<Request> {
+    /// Convert this request builder into the head of a request, if all of the required parts have been set
+    pub fn into_head(self) -> Option<RequestHead> {
         match self {
             RequestBuilder {
                 version: Some(version),
                 method: Some(method),
                 target: Some(target),
                 headers,
-                body,
-            } => Some(Request{
-                version, method, target, headers, body
-            }),
+                host,
+                body_length,
+            } => Some(RequestHead::new(version, method, target, headers, host, body_length)),
             _ => None,
         }
     }
@@ -407,26 +402,401 @@ impl RequestBuilder {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use http::status::StatusCode;
     use std::str::Bytes;
     use std::io;
 
     #[test]
-    fn test_parse_request_line() {
-        let mut builder = RequestBuilder::new();
-        let mut byte_iterator = StrReader::new("GET /test/path?k=v&k2 HTTP/1.1\r\n".bytes());
-        let mut it = StreamReader::from(&mut byte_iterator);
+    fn test_parse_from_reader() {
+        // A custom reader, standing in for a socket
+        let mut stream = StrReader::new(concat!(
+            "PUT /files/a.txt HTTP/1.1\r\nHost: example.com\r\nContent-Length: 11\r\n\r\n",
+            "hello world",
+        ).bytes());
+        let mut request = Request::from(&mut stream).unwrap();
+        assert_eq!(request.get_path(), "/files/a.txt");
+        assert_eq!(request.get_body().unwrap(), b"hello world");
+
+        // An in-memory buffer
+        let mut buffer: &[u8] = b"GET / HTTP/1.0\r\n\r\n";
+        assert_eq!(Request::from(&mut buffer).unwrap().get_version(), (1, 0));
+
+        let mut truncated: &[u8] = b"GET / HTTP/1.1\r\nHost: a\r\n";
+        match Request::from(&mut truncated) {
+            Err(ParseError::EOF) => (),
+            r => panic!("Expected EOF, got {:?}", r.map(|r| r.head)),
+        }
+    }
+
+    #[test]
+    fn test_parse_body_content_length() {
+        let mut byte_iterator = StrReader::new("Content-Length: 11\r\n\r\nhello world, and more".bytes());
+
+        with_body(&mut byte_iterator, |body| {
+            assert_eq!(body.get_length(), BodyLength::Fixed(11));
+            assert_eq!(body.get_body().unwrap(), b"hello world");
+            assert!(body.is_complete());
+        });
+    }
+
+    #[test]
+    fn test_parse_body_truncated() {
+        let mut byte_iterator = StrReader::new("Content-Length: 20\r\n\r\nhello world".bytes());
+
+        with_body(&mut byte_iterator, |body| {
+            match body.get_body() {
+                Err(ParseError::EOF) => (),
+                r => panic!("Expected EOF, got {:?}", r),
+            }
+        });
+    }
+
+    #[test]
+    fn test_parse_body_limit() {
+        let mut byte_iterator = StrReader::new("Content-Length: 11\r\n\r\nhello world".bytes());
+
+        with_body(&mut byte_iterator, |body| {
+            match body.get_body_with_limit(10) {
+                Err(ref e) if e.http_response_code() == Some(StatusCode::PAYLOAD_TOO_LARGE) => (),
+                r => panic!("Expected 413, got {:?}", r),
+            }
+        });
+    }
+
+    #[test]
+    fn test_parse_content_length_invalid() {
+        for value in &["", "-1", "+1", "1.5", "ten", "1 2", "99999999999999999999999"] {
+            match Request::parse_content_length(value) {
+                Err(ParseError::InvalidContentLength) => (),
+                r => panic!("Expected InvalidContentLength for {:?}, got {:?}", value, r),
+            }
+        }
+        assert_eq!(Request::parse_content_length("0").unwrap(), 0);
+        assert_eq!(Request::parse_content_length("1024").unwrap(), 1024);
+    }
 
-        Request::parse_request_line(&mut builder, &mut it).unwrap();
+    #[test]
+    fn test_parse_body_chunked() {
+        let mut byte_iterator = StrReader::new(concat!(
+            "Transfer-Encoding: chunked\r\n\r\n",
+            "5\r\nhello\r\n",
+            "1;ext\r\n \r\n",
+            "A ; name=value;quoted=\"a \\\"b\\\"\"\r\nworld, and\r\n",
+            "000\r\n",
+            "Expires: never\r\n\r\n",
+            "GET / HTTP/1.1\r\n",
+        ).bytes());
+
+        with_body(&mut byte_iterator, |body| {
+            assert_eq!(body.get_length(), BodyLength::Chunked);
+
+            // Read it in small pieces to check that it can be consumed incrementally
+            let mut data = Vec::new();
+            let mut buf = [0; 3];
+            loop {
+                match body.read(&mut buf).unwrap() {
+                    0 => break,
+                    n => data.extend_from_slice(&buf[..n]),
+                }
+            }
 
-        assert_eq!(builder, RequestBuilder {
-            version: Some((1, 1)),
-            method: Some(Method::Get),
-            target: Some(String::from("/test/path?k=v&k2")),
-            headers: HashMap::new(),
-            body: vec![],
+            assert_eq!(data, b"hello world, and".to_vec());
+            assert_eq!(body.get_trailers().get_str("expires"), Some("never"));
         });
     }
 
+    #[test]
+    fn test_parse_body_chunked_malformed() {
+        let cases = [
+            ("\r\nhello\r\n0\r\n\r\n", "InvalidChunkSize"),
+            ("x\r\n0\r\n\r\n", "InvalidChunkSize"),
+            ("11111111111111111\r\n", "InvalidChunkSize"),
+            ("5;\r\nhello\r\n0\r\n\r\n", "InvalidChunkExtension"),
+            ("5;a=\"b\r\nhello\r\n0\r\n\r\n", "InvalidChunkExtension"),
+            ("5\nhello\r\n0\r\n\r\n", "InvalidChunkExtension"),
+            ("5\r\nhello!\r\n0\r\n\r\n", "ChunkNotTerminated"),
+            ("5\r\nhello\r\n0\r\n", "EOF"),
+        ];
+        for &(body, expected) in &cases {
+            let request = format!("Transfer-Encoding: chunked\r\n\r\n{}", body);
+            let mut byte_iterator = StrReader::new(request.bytes());
+
+            with_body(&mut byte_iterator, |b| {
+                let result = b.get_body().map(|_| ());
+                assert_eq!(format!("{:?}", result), format!("Err({})", expected), "for body {:?}", body);
+            });
+        }
+
+        // The size line is limited in length, so a client can't keep sending extensions (or leading zeros) forever
+        for line in &[format!("1;{}\r\n", "a".repeat(10_000)), "0".repeat(10_000)] {
+            let request = format!("Transfer-Encoding: chunked\r\n\r\n{}", line);
+            let mut byte_iterator = StrReader::new(request.bytes());
+
+            with_body(&mut byte_iterator, |b| {
+                match b.get_body() {
+                    Err(ParseError::InvalidChunkExtension) => (),
+                    r => panic!("Expected InvalidChunkExtension, got {:?}", r),
+                }
+            });
+        }
+    }
+
+    #[test]
+    fn test_parser_limits() {
+        let config = ParserLimits {
+            max_method_length: 7,
+            max_target_length: 10,
+            max_header_size: 20,
+            max_header_count: 3,
+            max_header_section_size: 40,
+            max_body_size: Some(10),
+            obs_fold: ObsFoldPolicy::Reject,
+        };
+        let cases = [
+            ("OPTIONS /123456789 HTTP/1.0\r\nA: 12345678901234567\r\n\r\n", None),
+            ("PROPFIND / HTTP/1.0\r\n\r\n", Some(501)),
+            ("GET /1234567890 HTTP/1.0\r\n\r\n", Some(414)),
+            ("GET / HTTP/1.0\r\nA: 123456789012345678\r\n\r\n", Some(431)),
+            ("GET / HTTP/1.0\r\nA: 12345678901234567\r\nB: 1\r\n\r\n", None),
+            ("GET / HTTP/1.0\r\nA: 1\r\nB: 2\r\nC: 3\r\nD: 4\r\n\r\n", Some(431)),
+            ("GET / HTTP/1.0\r\nA: 1234567890\r\nB: 1234567890\r\nC: 1234567890\r\n\r\n", Some(431)),
+            ("POST / HTTP/1.0\r\nContent-Length: 10\r\n\r\n", None),
+            ("POST / HTTP/1.0\r\nContent-Length: 11\r\n\r\n", Some(413)),
+        ];
+        for &(request, expected) in &cases {
+            let result = parse_head(request, &config);
+            let status = result.err().and_then(|e| e.http_response_code()).map(u16::from);
+            assert_eq!(status, expected, "for {:?}", request);
+        }
+    }
+
+    #[test]
+    fn test_host_header() {
+        let config = ParserConfig::default();
+
+        let head = parse_head("GET / HTTP/1.1\r\nhost: Example.COM:8080\r\n\r\n", &config).unwrap();
+        assert_eq!(head.get_host().unwrap().to_string(), "example.com:8080");
+
+        let head = parse_head("GET / HTTP/1.1\r\nHost:\r\n\r\n", &config).unwrap();
+        assert_eq!(head.get_host(), None);
+
+        let head = parse_head("GET / HTTP/1.0\r\n\r\n", &config).unwrap();
+        assert_eq!(head.get_host(), None);
+
+        // The authority in an absolute form target overrides the Host header
+        let head = parse_head("GET http://[::1]:81/ HTTP/1.1\r\nHost: other\r\n\r\n", &config).unwrap();
+        assert_eq!(head.get_host().unwrap().to_string(), "[::1]:81");
+        assert_eq!(head.get_headers().get_str("Host"), Some("[::1]:81"));
+
+        match parse_head("GET / HTTP/1.1\r\n\r\n", &config) {
+            Err(ParseError::MissingRequiredHeader("Host")) => (),
+            r => panic!("Expected MissingRequiredHeader, got {:?}", r),
+        }
+        for request in &[
+            "GET / HTTP/1.1\r\nHost: a\r\nHost: a\r\n\r\n",
+            "GET / HTTP/1.1\r\nHost: a b\r\n\r\n",
+            "GET / HTTP/1.1\r\nHost: user@a\r\n\r\n",
+            "GET / HTTP/1.1\r\nHost: a:port\r\n\r\n",
+        ] {
+            match parse_head(request, &config) {
+                Err(ref e) if e.http_response_code() == Some(StatusCode::BAD_REQUEST) => (),
+                r => panic!("Expected 400 for {:?}, got {:?}", request, r),
+            }
+        }
+    }
+
+    #[test]
+    fn test_unsupported_version() {
+        for request in &["GET / HTTP/2.0\r\n\r\n", "GET / HTTP/0.9\r\n\r\n", "PRI * HTTP/2.0\r\n\r\n"] {
+            match parse_head(request, &ParserConfig::default()) {
+                Err(ParseError::UnsupportedVersion) => (),
+                r => panic!("Expected UnsupportedVersion for {:?}, got {:?}", request, r),
+            }
+        }
+    }
+
+    #[test]
+    fn test_expect() {
+        let head = parse_head("PUT / HTTP/1.1\r\nHost: a\r\nExpect: 100-Continue\r\nContent-Length: 1\r\n\r\n",
+                              &ParserConfig::default()).unwrap();
+        assert!(head.expects_continue());
+
+        // There's nothing to wait for if there's no body
+        let head = parse_head("GET / HTTP/1.1\r\nHost: a\r\nExpect: 100-continue\r\n\r\n", &ParserConfig::default());
+        assert!(!head.unwrap().expects_continue());
+        let head = parse_head("PUT / HTTP/1.1\r\nHost: a\r\nExpect: 100-continue\r\nContent-Length: 0\r\n\r\n",
+                              &ParserConfig::default());
+        assert!(!head.unwrap().expects_continue());
+
+        for request in &["GET / HTTP/1.1\r\nHost: a\r\nExpect: 200-ok\r\n\r\n",
+                         "GET / HTTP/1.1\r\nHost: a\r\nExpect: 100-continue\r\nExpect: 100-continue=1\r\n\r\n"] {
+            match parse_head(request, &ParserConfig::default()) {
+                Err(ref e) if e.http_response_code() == Some(StatusCode::EXPECTATION_FAILED) => (),
+                r => panic!("Expected 417 for {:?}, got {:?}", request, r),
+            }
+        }
+
+        // HTTP/1.0 clients may send an expectation, but it is ignored
+        let head = parse_head("PUT / HTTP/1.0\r\nExpect: 200-ok, 100-continue\r\nContent-Length: 1\r\n\r\n",
+                              &ParserConfig::default()).unwrap();
+        assert!(!head.expects_continue());
+    }
+
+    #[test]
+    fn test_version_semantics() {
+        let cases = [
+            ("GET / HTTP/1.1\r\nHost: a\r\n\r\n", (1, 1), true, true),
+            ("GET / HTTP/1.1\r\nHost: a\r\nConnection: Upgrade, Close\r\n\r\n", (1, 1), false, true),
+            ("GET / HTTP/1.2\r\nHost: a\r\n\r\n", (1, 1), true, true),
+            ("GET / HTTP/1.0\r\n\r\n", (1, 0), false, false),
+            ("GET / HTTP/1.0\r\nConnection: Keep-Alive\r\n\r\n", (1, 0), true, false),
+            ("GET / HTTP/1.0\r\nConnection: keep-alive, close\r\n\r\n", (1, 0), false, false),
+        ];
+        for &(request, response_version, persistent, chunked) in &cases {
+            let head = parse_head(request, &ParserConfig::default()).unwrap();
+
+            assert_eq!(head.get_response_version(), response_version);
+            assert_eq!(head.is_persistent(), persistent);
+            assert_eq!(head.allows_chunked_response(), chunked);
+        }
+    }
+
+    #[test]
+    fn test_opaque_header_values() {
+        let request = b"GET / HTTP/1.1\r\nHost: a\r\n\
+            Content-Disposition: attachment; filename=\"r\xc3\xa9sum\xc3\xa9.pdf\"\r\n\
+            X-Legacy: caf\xe9 \r\n\r\n";
+        let head = match Parser::new().parse(request).unwrap() {
+            Status::Complete(head, _) => head,
+            Status::NeedMore => panic!("Expected the head to be complete"),
+        };
+        let headers = head.get_headers();
+
+        assert_eq!(headers.get_str("Content-Disposition"), Some("attachment; filename=\"résumé.pdf\""));
+        assert_eq!(headers.get_str("X-Legacy"), None);
+        assert_eq!(headers.get("X-Legacy").map(HeaderValue::as_bytes), Some(&b"caf\xe9"[..]));
+    }
+
+    #[test]
+    fn test_obs_fold() {
+        let request = "GET / HTTP/1.1\r\nHost: a\r\nX-Long: first \r\n \t second\r\n\tthird\r\nX-Next: 1\r\n\r\n";
+
+        match parse_head(request, &ParserConfig::default()) {
+            Err(ParseError::ObsFold) => (),
+            r => panic!("Expected ObsFold, got {:?}", r),
+        }
+
+        let config = ParserConfig {
+            obs_fold: ObsFoldPolicy::Replace,
+            .. ParserConfig::default()
+        };
+        let head = parse_head(request, &config).unwrap();
+        assert_eq!(head.get_headers().get_str("X-Long"), Some("first second third"));
+        assert_eq!(head.get_headers().get_str("X-Next"), Some("1"));
+        assert_eq!(head.get_headers().len(), 3);
+
+        // Whitespace before the first field isn't a fold, because there is no field to continue
+        match parse_head("GET / HTTP/1.1\r\n Host: a\r\n\r\n", &config) {
+            Err(ParseError::IllegalCharacter) => (),
+            r => panic!("Expected IllegalCharacter, got {:?}", r),
+        }
+    }
+
+    /// Known request smuggling payloads, which must all be rejected with 400 Bad Request. Each one is a request that
+    /// some server could frame differently from another, so that the second half is treated as a new request.
+    const SMUGGLING_CORPUS: &[(&str, &str)] = &[
+        ("CL.TE", "POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 13\r\nTransfer-Encoding: chunked\r\n\r\n\
+            0\r\n\r\nSMUGGLED"),
+        ("TE.CL", "POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: chunked\r\nContent-Length: 3\r\n\r\n\
+            8\r\nSMUGGLED\r\n0\r\n\r\n"),
+        ("TE with CL 0", "POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 0\r\nTransfer-Encoding: chunked\r\n\r\n"),
+        ("TE.TE duplicate", "POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: chunked\r\n\
+            Transfer-Encoding: x\r\n\r\n"),
+        ("TE.TE identity", "POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: chunked, identity\r\n\r\n"),
+        ("TE chunked twice", "POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: chunked, chunked\r\n\r\n"),
+        ("TE identity", "POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: identity\r\n\r\n"),
+        ("TE prefix", "POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: xchunked\r\n\r\n"),
+        ("TE quoted", "POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: \"chunked\"\r\n\r\n"),
+        ("TE empty", "POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding:\r\n\r\n"),
+        ("TE space before colon", "POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding : chunked\r\n\r\n"),
+        ("TE tab before colon", "POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding\t: chunked\r\n\r\n"),
+        ("TE vertical tab", "POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding:\x0bchunked\r\n\r\n"),
+        ("TE NUL", "POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: chunked\x00\r\n\r\n"),
+        ("TE leading space", "POST / HTTP/1.1\r\n Transfer-Encoding: chunked\r\nHost: a\r\n\r\n"),
+        ("TE folded", "POST / HTTP/1.1\r\nHost: a\r\nX: y\r\n Transfer-Encoding: chunked\r\n\r\n"),
+        ("TE bare LF", "POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: chunked\nX: y\r\n\r\n"),
+        ("TE on HTTP/1.0", "POST / HTTP/1.0\r\nTransfer-Encoding: chunked\r\n\r\n"),
+        ("CL.CL", "POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 5\r\nContent-Length: 6\r\n\r\n"),
+        ("CL list", "POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 5, 6\r\n\r\n"),
+        ("CL plus", "POST / HTTP/1.1\r\nHost: a\r\nContent-Length: +5\r\n\r\n"),
+        ("CL negative", "POST / HTTP/1.1\r\nHost: a\r\nContent-Length: -5\r\n\r\n"),
+        ("CL hex", "POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 0x5\r\n\r\n"),
+        ("CL decimal", "POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 5.0\r\n\r\n"),
+        ("CL inner space", "POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 5 5\r\n\r\n"),
+        ("CL empty", "POST / HTTP/1.1\r\nHost: a\r\nContent-Length:\r\n\r\n"),
+        ("CL overflow", "POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 18446744073709551616\r\n\r\n"),
+        ("CL space before colon", "POST / HTTP/1.1\r\nHost: a\r\nContent-Length : 5\r\n\r\n"),
+    ];
+
+    #[test]
+    fn test_smuggling_corpus() {
+        for &(name, request) in SMUGGLING_CORPUS {
+            match parse_head(request, &ParserConfig::default()) {
+                Err(ref e) if e.http_response_code() == Some(StatusCode::BAD_REQUEST) => (),
+                r => panic!("Expected 400 for {}, got {:?}", name, r.map(|h| h.get_body_length())),
+            }
+        }
+    }
+
+    #[test]
+    fn test_unambiguous_framing() {
+        let cases = [
+            ("POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 5 \t\r\nContent-Length: 5\r\n\r\n", BodyLength::Fixed(5)),
+            ("POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: Chunked\t\r\n\r\n", BodyLength::Chunked),
+            ("POST / HTTP/1.0\r\nContent-Length: 0\r\n\r\n", BodyLength::Fixed(0)),
+            ("GET / HTTP/1.1\r\nHost: a\r\n\r\n", BodyLength::Empty),
+        ];
+        for &(request, expected) in &cases {
+            assert_eq!(parse_head(request, &ParserConfig::default()).unwrap().get_body_length(), expected);
+        }
+    }
+
+    /// Parse the head of `request` (everything except the body), in the same way as [`Request::from_with_config`]
+    fn parse_head(request: &str, config: &ParserConfig) -> Result<RequestHead, ParseError> {
+        match Parser::with_config(config).parse(request.as_bytes())? {
+            Status::Complete(head, _) => Ok(head),
+            Status::NeedMore => Err(ParseError::EOF),
+        }
+    }
+
+    #[test]
+    fn test_chunked_body_limit() {
+        let config = ParserConfig {
+            max_body_size: Some(10),
+            .. ParserConfig::default()
+        };
+        let mut byte_iterator = StrReader::new("5\r\nhello\r\n5\r\nworld\r\n1\r\n!\r\n0\r\n\r\n".bytes());
+        let stream: &mut dyn Read = &mut byte_iterator;
+        let mut body = Body::new(StreamReader::from(stream), BodyLength::Chunked, &config);
+
+        match body.get_body() {
+            Err(ParseError::BodyTooLarge) => (),
+            r => panic!("Expected BodyTooLarge, got {:?}", r),
+        }
+    }
+
+    /// Parse the headers from `stream`, then pass the body that follows them to `f`
+    fn with_body<F: FnOnce(&mut Body)>(stream: &mut dyn Read, f: F) {
+        let mut builder = RequestBuilder::new();
+        let mut it = StreamReader::from(stream);
+
+        Request::parse_fields(&mut it, &ParserConfig::default(), |name, value| builder.add_header(name, value))
+            .unwrap();
+        Request::parse_body(&mut builder, &ParserConfig::default()).unwrap();
+
+        f(&mut Body::new(it, builder.body_length, &ParserConfig::default()));
+    }
+
     struct StrReader<'a> {
         data: Bytes<'a>,
     }
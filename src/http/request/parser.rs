@@ -0,0 +1,579 @@
+//! A resumable, sans-IO parser for the head of a request (the request line and the headers)
+//!
+//! The parser doesn't do any IO itself. Instead, bytes are pushed into it in chunks of any size as they arrive, and
+//! it reports when it has seen the whole head. This means that the same parser can be driven by a blocking stream (as
+//! [`Request::from`] does) or by an event loop using non-blocking sockets.
+
+use std::mem;
+
+use http::header::HeaderValue;
+
+use super::util::{TokenType, TCHAR, TARGET_CHAR, FIELD_CHAR, scan_while, scan_until};
+use super::util::TokenType::{TChar, Invalid};
+use super::{Request, RequestBuilder, RequestHead, RequestTarget, Method, ParserConfig, ObsFoldPolicy, ParseError};
+
+/// The result of pushing bytes into a [`Parser`]
+// The head is only returned once per request, so there's nothing to be gained by boxing it
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug)]
+pub enum Status {
+    /// The head isn't complete yet. Every byte that was pushed has been consumed, so the next call should continue
+    /// with the bytes that follow them.
+    NeedMore,
+    /// The head is complete. The `usize` is the number of bytes from the last chunk that were part of the head, and
+    /// any bytes after that are the start of the body (or of the next request).
+    Complete(RequestHead, usize),
+}
+
+/// A push parser for the head of a request, as defined in [RFC 7230 §3](https://tools.ietf.org/html/rfc7230#section-3).
+///
+/// Once a head has been completed, the parser is ready to parse the next request on the same connection. After an
+/// error, the connection is in an unknown state and should be closed, so the parser must not be used again.
+///
+/// # Examples
+/// ```
+/// # use webserver::http::request::{Parser, Status};
+/// let mut parser = Parser::new();
+/// assert!(matches!(parser.parse(b"GET / HTTP/1.1\r\nHo").unwrap(), Status::NeedMore));
+/// match parser.parse(b"st: example.com\r\n\r\nbody").unwrap() {
+///     Status::Complete(head, consumed) => assert_eq!(consumed, 19),
+///     Status::NeedMore => unreachable!(),
+/// }
+/// ```
+pub struct Parser {
+    config: ParserConfig,
+    state: State,
+    builder: RequestBuilder,
+}
+
+/// The part of the head that the parser is currently in
+enum State {
+    /// Currently parsing the method, at the start of the request line
+    Method {method: Vec<u8>},
+    /// Currently parsing the target
+    Target {method: Method, target: Vec<u8>},
+    /// Currently parsing `HTTP/x.y` and the line ending, `index` bytes in
+    Version {method: Method, target: Vec<u8>, index: usize, version: (u8, u8)},
+    /// Currently parsing the header fields
+    Headers {fields: FieldParser},
+    /// An error has occurred
+    Failed,
+}
+
+impl Parser {
+    /// Construct a new parser, using the default [`ParserConfig`]
+    pub fn new() -> Parser {
+        Parser::with_config(&ParserConfig::default())
+    }
+
+    /// Construct a new parser, which will reject any request that exceeds the limits in `config`
+    pub fn with_config(config: &ParserConfig) -> Parser {
+        Parser {
+            config: *config,
+            state: State::Method {method: Vec::new()},
+            builder: RequestBuilder::new(),
+        }
+    }
+
+    /// Get the configuration that this parser uses
+    pub fn get_config(&self) -> &ParserConfig {
+        &self.config
+    }
+
+    /// Push the next chunk of bytes into the parser. If this completes the head, it is validated and returned along
+    /// with the number of bytes of `data` that it used, and the parser is reset for the next request.
+    pub fn parse(&mut self, data: &[u8]) -> Result<Status, ParseError> {
+        self.parse_with(data, true)
+    }
+
+    /// Push the next chunk of bytes in the same way as [`parse`](#method.parse), but pass every byte through the
+    /// state machine one at a time instead of scanning runs of bytes in bulk. This is only useful for measuring how
+    /// much faster the bulk scanning is.
+    #[doc(hidden)]
+    pub fn parse_per_byte(&mut self, data: &[u8]) -> Result<Status, ParseError> {
+        self.parse_with(data, false)
+    }
+
+    /// Push the next chunk of bytes, scanning runs of bytes in bulk if `bulk` is `true`
+    fn parse_with(&mut self, data: &[u8], bulk: bool) -> Result<Status, ParseError> {
+        let mut i = 0;
+        while i < data.len() {
+            // Take whole runs of ordinary bytes at once, and only handle the delimiters between them one at a time
+            let run = if bulk { self.push_run(&data[i..])? } else { 0 };
+            if run > 0 {
+                i += run;
+                continue;
+            }
+
+            if self.push(data[i])? {
+                return Ok(Status::Complete(self.finish()?, i + 1));
+            }
+            i += 1;
+        }
+        Ok(Status::NeedMore)
+    }
+
+    /// Consume the longest run at the start of `data` that can be handled in bulk (such as the characters of the
+    /// method or target, or the whole version), returning its length. The run stops before any byte that would be
+    /// rejected, so that [`push`](#method.push) reports the error.
+    fn push_run(&mut self, data: &[u8]) -> Result<usize, ParseError> {
+        let run = match self.state {
+            State::Method {ref mut method} => {
+                let run = scan_while(data, &TCHAR).min(self.config.max_method_length.saturating_sub(method.len()));
+                method.extend_from_slice(&data[..run]);
+                run
+            },
+            State::Target {ref mut target, ..} => {
+                let run = scan_until(data, b' ', &TARGET_CHAR)
+                    .min(self.config.max_target_length.saturating_sub(target.len()));
+                target.extend_from_slice(&data[..run]);
+                run
+            },
+            State::Version {index: 0, ..} if data.len() >= 10 && data.starts_with(b"HTTP/") &&
+                data[5].is_ascii_digit() && data[6] == b'.' && data[7].is_ascii_digit() && &data[8..10] == b"\r\n" => {
+                if let State::Version {method, target, ..} = mem::replace(&mut self.state, State::Failed) {
+                    self.finish_request_line(method, target, (data[5] - b'0', data[7] - b'0'))?;
+                }
+                self.state = State::Headers {fields: FieldParser::new()};
+                10
+            },
+            State::Headers {ref mut fields} => fields.push_run(data, &self.config),
+            _ => 0,
+        };
+        Ok(run)
+    }
+
+    /// Push a single byte, returning `true` if it was the last byte of the head
+    fn push(&mut self, b: u8) -> Result<bool, ParseError> {
+        self.state = match mem::replace(&mut self.state, State::Failed) {
+            // The method is 1 or more tchars, treated case-sensitively, and followed by a single space (according to
+            // [RFC 7230 §3.1.1](https://tools.ietf.org/html/rfc7230#section-3.1.1))
+            State::Method {mut method} => match TokenType::from(b) {
+                TChar(_) if method.len() >= self.config.max_method_length => return Err(ParseError::MethodTooLong),
+                TChar(c) => {
+                    method.push(c);
+                    State::Method {method}
+                },
+                Invalid(b' ') if method.is_empty() => return Err(ParseError::IllegalCharacter),
+                Invalid(b' ') => State::Target {method: Method::from(method), target: Vec::new()},
+                Invalid(_) => return Err(ParseError::IllegalCharacter),
+            },
+            // The most general form of the target is 1 or more visible characters (followed by a single space),
+            // though the allowed forms are checked once it is complete, as defined in
+            // [RFC 7230 §5.3](https://tools.ietf.org/html/rfc7230#section-5.3)
+            State::Target {method, mut target} => {
+                if target.len() >= self.config.max_target_length && b != b' ' {
+                    return Err(ParseError::TargetTooLong);
+                }
+                match b {
+                    // Allowed characters in URLs per [RFC 3986](https://tools.ietf.org/html/rfc3986#appendix-A)
+                    b'!' | b'#'..=b';' | b'=' | b'?'..=b'[' | b']'..=b'z' | b'|' | b'~' => {
+                        target.push(b);
+                        State::Target {method, target}
+                    },
+                    b' ' => State::Version {method, target, index: 0, version: (0, 0)},
+                    _ => return Err(ParseError::IllegalCharacter),
+                }
+            },
+            // The version should be HTTP/maj.min, where maj and min are single digits, as defined in
+            // [RFC 7230 §2.6](https://tools.ietf.org/html/rfc7230#section-2.6), and it ends the request line
+            State::Version {method, target, index, mut version} => {
+                match (b"HTTP/0.0\r\n"[index], b) {
+                    (b'0', n @ b'0'..=b'9') if index == 5 => version.0 = n - b'0',
+                    (b'0', n @ b'0'..=b'9') => version.1 = n - b'0',
+                    (expected, b) if expected == b && expected != b'0' => (),
+                    _ => return Err(ParseError::IllegalCharacter),
+                }
+                if index < 9 {
+                    State::Version {method, target, index: index + 1, version}
+                } else {
+                    self.finish_request_line(method, target, version)?;
+                    State::Headers {fields: FieldParser::new()}
+                }
+            },
+            State::Headers {mut fields} => {
+                let builder = &mut self.builder;
+                if fields.push(b, &self.config, &mut |name, value| builder.add_header(name, value))? {
+                    return Ok(true);
+                }
+                State::Headers {fields}
+            },
+            State::Failed => return Err(ParseError::new_server_error("Parser used after an error")),
+        };
+        Ok(false)
+    }
+
+    /// Check the request line once it has been read, and store it in the builder
+    fn finish_request_line(&mut self, method: Method, target: Vec<u8>, version: (u8, u8)) -> Result<(), ParseError> {
+        // Only HTTP/1.x is supported, because other major versions use a different syntax, so this is checked before
+        // trying to interpret the rest of the line
+        if version.0 != 1 {
+            return Err(ParseError::UnsupportedVersion);
+        }
+        self.builder.set_version(version.0, version.1);
+
+        // The target may only be used in certain forms, depending on the method. It is safe to convert it to UTF-8
+        // because only ASCII characters were accepted.
+        self.builder.set_target(RequestTarget::parse(&method, String::from_utf8(target).unwrap())?);
+        self.builder.set_method(method);
+
+        Ok(())
+    }
+
+    /// Validate the head once all of it has been read, and reset the parser for the next request. If the head is
+    /// invalid, the parser is left in the failed state.
+    fn finish(&mut self) -> Result<RequestHead, ParseError> {
+        let mut builder = mem::replace(&mut self.builder, RequestBuilder::new());
+
+        // Sanity checks
+        Request::validate_host(&mut builder)?;
+        Request::validate_expect(&builder)?;
+        Request::parse_body(&mut builder, &self.config)?;
+
+        self.state = State::Method {method: Vec::new()};
+        Ok(builder.into_head().unwrap())
+    }
+}
+
+impl Default for Parser {
+    fn default() -> Parser {
+        Parser::new()
+    }
+}
+
+
+/// A push parser for a block of header fields followed by an empty line, as specified in
+/// [RFC 7230 §3.2](https://tools.ietf.org/html/rfc7230#section-3.2). This is used for both the headers and the
+/// trailers of a chunked body, which share the same syntax.
+pub struct FieldParser {
+    state: FieldState,
+    // The number of fields and their sizes, so the limits can be enforced
+    field_count: usize,
+    sizes: FieldSizes,
+}
+
+/// The number of bytes in a block of header fields, and in the field that is currently being parsed. This is shared
+/// by the [`FieldParser`] and [`RequestRef`](super::RequestRef), so that they enforce the limits in the same way.
+#[derive(Default)]
+pub struct FieldSizes {
+    section: usize,
+    field: usize,
+}
+
+impl FieldSizes {
+    /// Count a byte that is being handled on its own, where `line_start` is `true` if it is the first byte on its
+    /// line. A line that doesn't start with whitespace starts a new field, so the size of the previous field is
+    /// forgotten before this byte is counted, but a continuation line (obs-fold) is part of the previous field.
+    pub fn count_byte(&mut self, b: u8, line_start: bool, config: &ParserConfig) -> Result<(), ParseError> {
+        self.section += 1;
+        if self.section > config.max_header_section_size {
+            return Err(ParseError::HeaderSectionTooLarge);
+        }
+        if line_start && b != b' ' && b != b'\t' {
+            self.field = 0;
+        }
+        // The size of a field doesn't include the line endings
+        if b != b'\r' && b != b'\n' {
+            self.field += 1;
+            if self.field > config.max_header_size {
+                return Err(ParseError::HeaderTooLarge);
+            }
+        }
+        Ok(())
+    }
+
+    /// Get the length of the longest run of bytes within a field that can be counted without exceeding a limit
+    pub fn run_limit(&self, config: &ParserConfig) -> usize {
+        config.max_header_section_size.saturating_sub(self.section)
+            .min(config.max_header_size.saturating_sub(self.field))
+    }
+
+    /// Count a run of bytes within a field, which must be no longer than [`run_limit`](#method.run_limit)
+    pub fn count_run(&mut self, run: usize) {
+        self.section += run;
+        self.field += run;
+    }
+
+    /// Start counting a new field, which is used when the first byte of its line is counted as part of a run
+    fn start_field(&mut self) {
+        self.field = 0;
+    }
+}
+
+// An enum to store the current state of the parser
+enum FieldState {
+    // After a new line, ready to parse the header name. The previous field is held back, because it may be
+    // continued on this line if obs-fold is permitted
+    Start {pending: Option<(String, Vec<u8>)>},
+    // Currently parsing the header name
+    Name {name: Vec<u8>},
+    // Currently parsing the whitespace after the : but before the value
+    ValueLeadingWS {name: String},
+    // Currently parsing the value
+    Value {name: String, value: Vec<u8>},
+    // Currently parsing the whitespace at the start of a continuation line (obs-fold)
+    FoldWS {name: String, value: Vec<u8>},
+    // Currently parsing the new line (CR (here) LF)
+    NewLine {name: String, value: Vec<u8>},
+    // Currently parsing the final new line (CR LF CR (here) LF)
+    FinalNewLine,
+}
+
+impl FieldParser {
+    /// Construct a new `FieldParser`, ready for the first field
+    pub fn new() -> FieldParser {
+        FieldParser {
+            state: FieldState::Start {pending: None},
+            field_count: 0,
+            sizes: FieldSizes::default(),
+        }
+    }
+
+    /// Consume the longest run at the start of `data` that doesn't complete a field, such as a name or a value along
+    /// with the whitespace before it, returning its length. Like [`Parser::push_run`], this stops before any byte that
+    /// would exceed a limit.
+    pub fn push_run(&mut self, data: &[u8], config: &ParserConfig) -> usize {
+        let mut consumed = 0;
+        loop {
+            let data = &data[consumed..];
+            let limit = self.sizes.run_limit(config);
+            let (run, field) = match self.state {
+                // A new field is starting, so move straight into its name
+                FieldState::Start {pending: None} if data.first().is_some_and(|&b| TCHAR[b as usize]) => {
+                    self.sizes.start_field();
+                    self.state = FieldState::Name {name: Vec::new()};
+                    continue;
+                },
+                FieldState::ValueLeadingWS {ref mut name} => {
+                    let run = data.iter().take_while(|&&b| b == b' ' || b == b'\t').count().min(limit);
+                    self.sizes.count_run(run);
+                    consumed += run;
+                    if run == data.len() || run == limit {
+                        return consumed;
+                    }
+                    let name = mem::take(name);
+                    self.state = FieldState::Value {name, value: Vec::new()};
+                    continue;
+                },
+                FieldState::Name {ref mut name} => (scan_while(data, &TCHAR), name),
+                FieldState::Value {ref mut value, ..} => (scan_until(data, b'\r', &FIELD_CHAR), value),
+                _ => return consumed,
+            };
+            let run = run.min(limit);
+
+            field.extend_from_slice(&data[..run]);
+            self.sizes.count_run(run);
+            return consumed + run;
+        }
+    }
+
+    /// Push the next byte, passing each field to `add_field` once it is complete. Returns `true` once the empty line
+    /// at the end of the block has been parsed.
+    pub fn push<F>(&mut self, b: u8, config: &ParserConfig, add_field: &mut F) -> Result<bool, ParseError>
+        where F: FnMut(String, HeaderValue) {
+        let line_start = matches!(self.state, FieldState::Start {..});
+        self.sizes.count_byte(b, line_start, config)?;
+
+        let mut state = mem::replace(&mut self.state, FieldState::FinalNewLine);
+        // Wrap this in a loop so that we can cheaply transition to a different state without having consumed any
+        // characters
+        loop {
+            match state {
+                FieldState::Start {pending: Some((n, v))} => match b {
+                    // A line starting with whitespace continues the previous field (obs-fold, RFC 7230 §3.2.4)
+                    b' ' | b'\t' => match config.obs_fold {
+                        ObsFoldPolicy::Reject => return Err(ParseError::ObsFold),
+                        ObsFoldPolicy::Replace => {
+                            state = FieldState::FoldWS {name: n, value: v};
+                        },
+                    },
+                    _ => {
+                        // The field doesn't continue onto this line, so it can be stored
+                        self.field_count += 1;
+                        if self.field_count > config.max_header_count {
+                            return Err(ParseError::TooManyHeaders);
+                        }
+                        add_field(n, HeaderValue::from(v));
+                        state = FieldState::Start {pending: None};
+                        continue;
+                    },
+                },
+                FieldState::Start {pending: None} => match b {
+                    b'\r' => state = FieldState::FinalNewLine,
+                    _ => {
+                        // Move straight into Name without consuming this character
+                        state = FieldState::Name {
+                            name: Vec::new()
+                        };
+                        continue;
+                    }
+                },
+                FieldState::Name {name: mut n} => match TokenType::from(b) {
+                    TChar(c) => {
+                        n.push(c);
+                        state = FieldState::Name {name: n}
+                    },
+                    Invalid(b':') => {
+                        // Safe to convert to UTF-8 because it was constructed from just ASCII characters
+                        let name = String::from_utf8(n).unwrap();
+                        state = FieldState::ValueLeadingWS {name};
+                    },
+                    Invalid(_) => return Err(ParseError::IllegalCharacter),
+                },
+                FieldState::ValueLeadingWS {name: n} => match b {
+                    b' ' | b'\t' => state = FieldState::ValueLeadingWS {name: n},
+                    _ => {
+                        // Move straight into Value without consuming
+                        state = FieldState::Value {
+                            name: n,
+                            value: Vec::new()
+                        };
+                        continue;
+                    }
+                },
+                FieldState::FoldWS {name: n, value: mut v} => match b {
+                    b' ' | b'\t' => state = FieldState::FoldWS {name: n, value: v},
+                    _ => {
+                        // The whole fold is replaced by a single space, then the value continues
+                        v.push(b' ');
+                        state = FieldState::Value {name: n, value: v};
+                        continue;
+                    }
+                },
+                FieldState::Value {name: n, value: mut v} => match b {
+                    // Bytes outside of US-ASCII (obs-text) are kept as opaque data, as the specification says
+                    b'\t' | b' '..=b'~' | 0x80..=0xFF => {
+                        v.push(b);
+                        state = FieldState::Value {name: n, value: v};
+                    },
+                    b'\r' => {
+                        // Trailing whitespace isn't part of the value
+                        while v.last() == Some(&b' ') || v.last() == Some(&b'\t') {
+                            v.pop();
+                        }
+                        // Transition to expect the LF
+                        state = FieldState::NewLine {name: n, value: v};
+                    },
+                    _ => return Err(ParseError::IllegalCharacter),
+                },
+                FieldState::NewLine {name: n, value: v} => match b {
+                    b'\n' => state = FieldState::Start {pending: Some((n, v))},
+                    _ => return Err(ParseError::IllegalCharacter),
+                },
+                FieldState::FinalNewLine => match b {
+                    b'\n' => return Ok(true),
+                    _ => return Err(ParseError::IllegalCharacter),
+                }
+            }
+
+            // Consume the next character
+            break;
+        }
+
+        self.state = state;
+        Ok(false)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::header::HeaderMap;
+    use http::request::BodyLength;
+
+    #[test]
+    fn test_parse_request_line() {
+        let mut parser = Parser::new();
+
+        assert!(matches!(parser.parse(b"GET /test/path?k=v&k2 HTTP/1.1\r\n").unwrap(), Status::NeedMore));
+
+        assert_eq!(parser.builder, RequestBuilder {
+            version: Some((1, 1)),
+            method: Some(Method::Get),
+            target: Some(RequestTarget::parse(&Method::Get, String::from("/test/path?k=v&k2")).unwrap()),
+            headers: HeaderMap::new(),
+            host: None,
+            body_length: BodyLength::Empty,
+        });
+
+        let target = parser.builder.target.unwrap();
+        assert_eq!(target.get_path(), "/test/path");
+        assert_eq!(target.get_query(), Some("k=v&k2"));
+    }
+
+    #[test]
+    fn test_partial_input() {
+        let request = b"POST /upload HTTP/1.1\r\nHost: example.com\r\nContent-Length: 5\r\n\r\nhelloGET / HTTP/1.1\r\n";
+        let head_length = request.len() - "helloGET / HTTP/1.1\r\n".len();
+
+        // However the input is split up, the result must be the same
+        for chunk_size in 1..request.len() {
+            let mut parser = Parser::new();
+            let mut offset = 0;
+            let (head, consumed) = loop {
+                let chunk = &request[offset..request.len().min(offset + chunk_size)];
+                match parser.parse(chunk).unwrap() {
+                    Status::Complete(head, consumed) => break (head, consumed),
+                    Status::NeedMore => offset += chunk.len(),
+                }
+            };
+
+            assert_eq!(offset + consumed, head_length, "for chunk size {}", chunk_size);
+            assert_eq!(head.get_path(), "/upload");
+            assert_eq!(head.get_headers().get_str("Host"), Some("example.com"));
+            assert_eq!(head.get_body_length(), BodyLength::Fixed(5));
+        }
+    }
+
+    #[test]
+    fn test_per_byte() {
+        // Without bulk scanning, the result is the same
+        let request = b"POST /upload?a=b HTTP/1.1\r\nHost: example.com\r\nX-Empty:\r\nContent-Length: 5\r\n\r\nhello";
+        let bulk = Parser::new().parse(request).unwrap();
+        let per_byte = Parser::new().parse_per_byte(request).unwrap();
+        assert_eq!(format!("{:?}", per_byte), format!("{:?}", bulk));
+
+        match Parser::new().parse_per_byte(b"GET / HTTP/1.1\r\nHo st: a\r\n\r\n") {
+            Err(ParseError::IllegalCharacter) => (),
+            r => panic!("Expected IllegalCharacter, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_reuse() {
+        let mut parser = Parser::new();
+
+        match parser.parse(b"GET /a HTTP/1.1\r\nHost: a\r\n\r\nGET /b HTTP/1.1\r\nHost: b\r\n\r\n").unwrap() {
+            Status::Complete(head, consumed) => {
+                assert_eq!(head.get_path(), "/a");
+                assert_eq!(consumed, 28);
+            },
+            Status::NeedMore => panic!("Expected the first request to be complete"),
+        }
+        match parser.parse(b"GET /b HTTP/1.1\r\nHost: b\r\n\r\n").unwrap() {
+            Status::Complete(head, _) => assert_eq!(head.get_path(), "/b"),
+            Status::NeedMore => panic!("Expected the second request to be complete"),
+        }
+
+        // After an error, the parser refuses to continue
+        assert!(parser.parse(b"GET\t").is_err());
+        assert!(parser.parse(b"/ HTTP/1.1\r\n").is_err());
+
+        // Including when the error is only found once the whole head has been read
+        let mut parser = Parser::new();
+        match parser.parse(b"GET / HTTP/1.1\r\n\r\n") {
+            Err(ParseError::MissingRequiredHeader("Host")) => (),
+            r => panic!("Expected MissingRequiredHeader, got {:?}", r),
+        }
+        assert!(parser.parse(b"GET / HTTP/1.1\r\nHost: a\r\n\r\n").is_err());
+    }
+
+    #[test]
+    fn test_empty_method() {
+        match Parser::new().parse(b" / HTTP/1.1\r\nHost: a\r\n\r\n") {
+            Err(ParseError::IllegalCharacter) => (),
+            r => panic!("Expected IllegalCharacter, got {:?}", r),
+        }
+    }
+}
@@ -0,0 +1,151 @@
+//! Parsing of the query component of the request target into key-value pairs
+
+use std::slice;
+
+use super::util::{ParseError, percent_decode};
+
+/// The parameters from a query string such as `a=1&b=2&b=3&c`, using the `application/x-www-form-urlencoded`
+/// conventions that browsers use for forms.
+///
+/// Keys can be repeated, and every value is kept in the order that it appeared. A key without a value (`c` above) is
+/// stored with a value of `None`, which is different from a key with an empty value (`c=`), which has `Some("")`.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Query {
+    params: Vec<(String, Option<String>)>,
+}
+
+impl Query {
+    /// Parse a query string (without the leading `?`). The pairs are separated by `&`, and each key is separated
+    /// from its value by the first `=`. Both are then decoded by replacing `+` with a space and decoding
+    /// percent-encoded octets, which must form valid UTF-8.
+    pub fn parse(query: &str) -> Result<Query, ParseError> {
+        let mut params = Vec::new();
+        for pair in query.split('&').filter(|p| !p.is_empty()) {
+            let param = match pair.find('=') {
+                Some(i) => (form_decode(&pair[..i])?, Some(form_decode(&pair[i + 1..])?)),
+                None => (form_decode(pair)?, None),
+            };
+            params.push(param);
+        }
+
+        Ok(Query {
+            params,
+        })
+    }
+
+    /// Get the value of the first parameter with the given key.
+    ///
+    /// Returns `None` if the key isn't present, `Some(None)` if it is present without a value, and `Some(Some(v))` if
+    /// it has a value.
+    pub fn get(&self, key: &str) -> Option<Option<&str>> {
+        self.params.iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_deref())
+    }
+
+    /// Get the values of every parameter with the given key, in the order that they appeared
+    pub fn get_all<'a>(&'a self, key: &'a str) -> impl Iterator<Item = Option<&'a str>> + 'a {
+        self.params.iter()
+            .filter(move |(k, _)| k == key)
+            .map(|(_, v)| v.as_deref())
+    }
+
+    /// Returns `true` if there is at least one parameter with the given key
+    pub fn contains(&self, key: &str) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Get the number of parameters, counting each repeated key separately
+    pub fn len(&self) -> usize {
+        self.params.len()
+    }
+
+    /// Returns `true` if there are no parameters
+    pub fn is_empty(&self) -> bool {
+        self.params.is_empty()
+    }
+
+    /// Iterate over every parameter as `(key, value)`, in the order that they appeared
+    pub fn iter(&self) -> Iter<'_> {
+        Iter {
+            inner: self.params.iter(),
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a Query {
+    type Item = (&'a str, Option<&'a str>);
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Iter<'a> {
+        self.iter()
+    }
+}
+
+/// An iterator over the parameters in a [`Query`]
+pub struct Iter<'a> {
+    inner: slice::Iter<'a, (String, Option<String>)>,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = (&'a str, Option<&'a str>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(k, v)| (k.as_str(), v.as_deref()))
+    }
+}
+
+/// Decode a key or value, replacing `+` with a space before decoding percent-encoded octets (so `%2B` is still `+`)
+fn form_decode(input: &str) -> Result<String, ParseError> {
+    percent_decode(&input.replace('+', " "))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_query() {
+        let query = Query::parse("k=v&k2").unwrap();
+        assert_eq!(query.len(), 2);
+        assert_eq!(query.get("k"), Some(Some("v")));
+        assert_eq!(query.get("k2"), Some(None));
+        assert_eq!(query.get("k3"), None);
+        assert!(query.contains("k2"));
+    }
+
+    #[test]
+    fn test_empty_and_missing_values() {
+        let query = Query::parse("a=&b&&c==d&=e").unwrap();
+        assert_eq!(query.iter().collect::<Vec<_>>(), vec![
+            ("a", Some("")),
+            ("b", None),
+            ("c", Some("=d")),
+            ("", Some("e")),
+        ]);
+        assert!(Query::parse("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_repeated_keys() {
+        let query = Query::parse("tag=b&x=1&tag=a&tag").unwrap();
+        assert_eq!(query.get("tag"), Some(Some("b")));
+        assert_eq!(query.get_all("tag").collect::<Vec<_>>(), vec![Some("b"), Some("a"), None]);
+    }
+
+    #[test]
+    fn test_form_decoding() {
+        let query = Query::parse("name=J%C3%BCrgen+M%C3%BCller&sum=1%2B1+%3D+2&a%26b=c%3Dd").unwrap();
+        assert_eq!(query.get("name"), Some(Some("Jürgen Müller")));
+        assert_eq!(query.get("sum"), Some(Some("1+1 = 2")));
+        assert_eq!(query.get("a&b"), Some(Some("c=d")));
+
+        for raw in &["a=%", "a=%zz", "%FF=1", "a=%C3"] {
+            match Query::parse(raw) {
+                Err(ParseError::InvalidPercentEncoding) => (),
+                r => panic!("Expected InvalidPercentEncoding for {}, got {:?}", raw, r),
+            }
+        }
+    }
+}
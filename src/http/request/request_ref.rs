@@ -0,0 +1,451 @@
+//! A zero-copy view of the head of a request, borrowed from the buffer that it was read into
+
+use std::borrow::Cow;
+use std::str;
+
+use http::header::HeaderValue;
+
+use super::util::{TokenType, TCHAR, TARGET_CHAR, FIELD_CHAR, scan_while, scan_until};
+use super::util::TokenType::{TChar, Invalid};
+use super::parser::FieldSizes;
+use super::{framing, Request, RequestBuilder, RequestHead, RequestTarget, TargetForm, Method, BodyLength,
+            ParserConfig, ObsFoldPolicy, ParseError};
+
+/// The head of a request, where the method, target, and every header name and value are slices of the buffer that
+/// the request was parsed from, so nothing is copied.
+///
+/// Parsing does all of the same checks as the [`Parser`](struct.Parser.html), including the target, the `Host` and
+/// `Expect` headers and the framing of the body, so the two accept exactly the same requests. Those are checked in
+/// place, and only copied by [`to_head`](#method.to_head), which converts everything into an owned [`RequestHead`] for
+/// handlers that need to keep it.
+///
+/// Because the values are slices, folded header fields (see [`ObsFoldPolicy::Replace`]) can't be joined up front. The
+/// value of a folded field therefore still contains the line breaks, and they are replaced by spaces when it is
+/// copied.
+#[derive(Debug, Clone)]
+pub struct RequestRef<'buf> {
+    method: &'buf str,
+    target: &'buf str,
+    version: (u8, u8),
+    headers: Vec<(&'buf str, &'buf [u8])>,
+    body_length: BodyLength,
+    config: ParserConfig,
+}
+
+/// The part of a header line that is being parsed, tracked by position rather than by copying
+#[derive(Clone, Copy)]
+enum FieldState {
+    Start,
+    Name,
+    ValueLeadingWS,
+    Value,
+    FoldWS,
+    NewLine,
+    FinalNewLine,
+}
+
+impl<'buf> RequestRef<'buf> {
+    /// Parse the head of the request at the start of `buf`. Returns `None` if `buf` doesn't contain the whole head
+    /// yet, in which case it should be called again once more data has been read into the buffer. Otherwise the
+    /// number of bytes in the head is returned too, and any bytes after that are the start of the body.
+    pub fn parse(buf: &'buf [u8], config: &ParserConfig) -> Result<Option<(RequestRef<'buf>, usize)>, ParseError> {
+        // Request line, as defined in [RFC 7230 §3.1.1](https://tools.ietf.org/html/rfc7230#section-3.1.1)
+        let mut pos = scan_while(buf, &TCHAR);
+        if pos > config.max_method_length {
+            return Err(ParseError::MethodTooLong);
+        }
+        match buf.get(pos) {
+            None => return Ok(None),
+            // The method must have at least one character
+            Some(&b' ') if pos == 0 => return Err(ParseError::IllegalCharacter),
+            Some(&b' ') => (),
+            Some(_) => return Err(ParseError::IllegalCharacter),
+        }
+        let method = &buf[..pos];
+
+        let target_start = pos + 1;
+        pos = target_start + scan_until(&buf[target_start..], b' ', &TARGET_CHAR);
+        let length = pos - target_start;
+        match buf.get(pos) {
+            _ if length > config.max_target_length => return Err(ParseError::TargetTooLong),
+            None => return Ok(None),
+            Some(&b' ') => (),
+            Some(_) if length == config.max_target_length => return Err(ParseError::TargetTooLong),
+            Some(_) => return Err(ParseError::IllegalCharacter),
+        }
+        let target = &buf[target_start..pos];
+
+        pos += 1;
+        let mut version = (0, 0);
+        for (index, &expected) in b"HTTP/0.0\r\n".iter().enumerate() {
+            match (expected, buf.get(pos + index)) {
+                (_, None) => return Ok(None),
+                (b'0', Some(&n @ b'0'..=b'9')) if index == 5 => version.0 = n - b'0',
+                (b'0', Some(&n @ b'0'..=b'9')) => version.1 = n - b'0',
+                (expected, Some(&b)) if expected == b && expected != b'0' => (),
+                _ => return Err(ParseError::IllegalCharacter),
+            }
+        }
+        if version.0 != 1 {
+            return Err(ParseError::UnsupportedVersion);
+        }
+        pos += 10;
+
+        // The method and target only contain ASCII characters, so they are valid UTF-8
+        let method = str::from_utf8(method).unwrap();
+        let target = str::from_utf8(target).unwrap();
+        let (form, target_authority) = RequestTarget::validate(&Method::from(method.as_bytes().to_vec()), target)?;
+
+        // Header fields, as defined in [RFC 7230 §3.2](https://tools.ietf.org/html/rfc7230#section-3.2)
+        let (headers, end) = match RequestRef::parse_fields(buf, pos, config)? {
+            Some(result) => result,
+            None => return Ok(None),
+        };
+
+        // The same checks as the owned parser, so that both accept exactly the same requests
+        let target_authority = if form == TargetForm::Absolute { target_authority } else { None };
+        let hosts = headers.iter().filter(|&&(n, _)| n.eq_ignore_ascii_case("Host")).map(|&(_, v)| unfold(v));
+        Request::find_host(version, hosts, target_authority)?;
+        Request::check_expect(version, combine(&headers, "Expect").as_deref())?;
+
+        // The only headers that need to be interpreted before the body can be found are the ones that frame it
+        let content_length = combine(&headers, "Content-Length");
+        let transfer_encoding = combine(&headers, "Transfer-Encoding");
+        let body_length = framing::body_length_from(version, content_length.as_deref(), transfer_encoding.as_deref())?;
+        if let BodyLength::Fixed(n) = body_length {
+            if config.max_body_size.is_some_and(|max| n > max) {
+                return Err(ParseError::BodyTooLarge);
+            }
+        }
+
+        let request = RequestRef {
+            method,
+            target,
+            version,
+            headers,
+            body_length,
+            config: *config,
+        };
+        Ok(Some((request, end)))
+    }
+
+    /// Parse the header fields starting at `pos`, up to and including the empty line that ends them. This follows the
+    /// same rules as the [`Parser`](struct.Parser.html), but records where each name and value are instead of copying
+    /// them.
+    #[allow(clippy::type_complexity)]
+    fn parse_fields(buf: &'buf [u8], mut pos: usize, config: &ParserConfig)
+        -> Result<Option<(Vec<(&'buf str, &'buf [u8])>, usize)>, ParseError> {
+        let mut headers = Vec::new();
+        let mut state = FieldState::Start;
+        // The field that has been completed, but might still be continued by obs-fold
+        let mut pending = false;
+        let (mut name_start, mut name_end, mut value_start, mut value_end) = (0, 0, 0, 0);
+        let mut sizes = FieldSizes::default();
+
+        loop {
+            // Skip over whole runs of name or value characters at once, stopping before any byte that would exceed a
+            // limit so that it is reported below
+            let run = match state {
+                FieldState::Name => scan_while(&buf[pos..], &TCHAR),
+                FieldState::Value => scan_until(&buf[pos..], b'\r', &FIELD_CHAR),
+                _ => 0,
+            };
+            let run = run.min(sizes.run_limit(config));
+            if run > 0 {
+                if let FieldState::Value = state {
+                    // Trailing whitespace isn't part of the value
+                    if let Some(i) = buf[pos..pos + run].iter().rposition(|&b| b != b' ' && b != b'\t') {
+                        value_end = pos + i + 1;
+                    }
+                }
+                sizes.count_run(run);
+                pos += run;
+                continue;
+            }
+
+            let b = match buf.get(pos) {
+                None => return Ok(None),
+                Some(&b) => b,
+            };
+
+            sizes.count_byte(b, matches!(state, FieldState::Start), config)?;
+
+            // As in the owned parser, a state can be left without consuming the byte by continuing this loop
+            loop {
+                match state {
+                    FieldState::Start if pending => match b {
+                        b' ' | b'\t' => match config.obs_fold {
+                            ObsFoldPolicy::Reject => return Err(ParseError::ObsFold),
+                            ObsFoldPolicy::Replace => state = FieldState::FoldWS,
+                        },
+                        _ => {
+                            if headers.len() >= config.max_header_count {
+                                return Err(ParseError::TooManyHeaders);
+                            }
+                            // Names only contain tchars, so they are valid UTF-8
+                            let name = str::from_utf8(&buf[name_start..name_end]).unwrap();
+                            headers.push((name, &buf[value_start..value_end]));
+                            pending = false;
+                            continue;
+                        },
+                    },
+                    FieldState::Start => match b {
+                        b'\r' => state = FieldState::FinalNewLine,
+                        _ => {
+                            name_start = pos;
+                            state = FieldState::Name;
+                            continue;
+                        },
+                    },
+                    FieldState::Name => match TokenType::from(b) {
+                        TChar(_) => (),
+                        Invalid(b':') => {
+                            name_end = pos;
+                            state = FieldState::ValueLeadingWS;
+                        },
+                        Invalid(_) => return Err(ParseError::IllegalCharacter),
+                    },
+                    FieldState::ValueLeadingWS => match b {
+                        b' ' | b'\t' => (),
+                        _ => {
+                            value_start = pos;
+                            value_end = pos;
+                            state = FieldState::Value;
+                            continue;
+                        },
+                    },
+                    FieldState::FoldWS => match b {
+                        b' ' | b'\t' => (),
+                        _ => {
+                            state = FieldState::Value;
+                            continue;
+                        },
+                    },
+                    FieldState::Value => match b {
+                        // Trailing whitespace isn't part of the value, so the end only moves past other characters
+                        b' ' | b'\t' => (),
+                        b'!'..=b'~' | 0x80..=0xFF => value_end = pos + 1,
+                        b'\r' => state = FieldState::NewLine,
+                        _ => return Err(ParseError::IllegalCharacter),
+                    },
+                    FieldState::NewLine => match b {
+                        b'\n' => {
+                            pending = true;
+                            state = FieldState::Start;
+                        },
+                        _ => return Err(ParseError::IllegalCharacter),
+                    },
+                    FieldState::FinalNewLine => match b {
+                        b'\n' => return Ok(Some((headers, pos + 1))),
+                        _ => return Err(ParseError::IllegalCharacter),
+                    },
+                }
+
+                // Consume the next character
+                break;
+            }
+            pos += 1;
+        }
+    }
+
+    /// Get the request method
+    pub fn get_method(&self) -> &'buf str {
+        self.method
+    }
+    /// Get the request target exactly as it was sent
+    pub fn get_target(&self) -> &'buf str {
+        self.target
+    }
+    /// Get the request's HTTP version, in the format (major, minor)
+    pub fn get_version(&self) -> (u8, u8) {
+        self.version
+    }
+    /// Get every header field as `(name, value)`, in the order that they were sent
+    pub fn get_headers(&self) -> &[(&'buf str, &'buf [u8])] {
+        &self.headers
+    }
+    /// Get the value of the first header field with the given name, which is compared case-insensitively
+    pub fn get_header(&self, name: &str) -> Option<&'buf [u8]> {
+        self.headers.iter()
+            .find(|&&(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|&(_, v)| v)
+    }
+    /// Get the way that the length of the body is determined
+    pub fn get_body_length(&self) -> BodyLength {
+        self.body_length
+    }
+
+    /// Copy this into an owned [`RequestHead`], checking the target and the `Host` header in the same way as the
+    /// [`Parser`](struct.Parser.html)
+    pub fn to_head(&self) -> Result<RequestHead, ParseError> {
+        let method = Method::from(self.method.as_bytes().to_vec());
+        let mut builder = RequestBuilder::new();
+        builder.set_version(self.version.0, self.version.1);
+        builder.set_target(RequestTarget::parse(&method, self.target.to_string())?);
+        builder.set_method(method);
+        for &(name, value) in &self.headers {
+            builder.add_header(name.to_string(), HeaderValue::from(unfold(value).into_owned()));
+        }
+
+        Request::validate_host(&mut builder)?;
+        Request::validate_expect(&builder)?;
+        Request::parse_body(&mut builder, &self.config)?;
+
+        Ok(builder.into_head().unwrap())
+    }
+}
+
+/// Get the combined value of every field with the given name (see [`HeaderMap::get_combined`]), without copying
+/// unless there is more than one field or the value has been folded
+///
+/// [`HeaderMap::get_combined`]: ../header/struct.HeaderMap.html#method.get_combined
+fn combine<'buf>(headers: &[(&str, &'buf [u8])], name: &str) -> Option<Cow<'buf, [u8]>> {
+    let mut values = headers.iter().filter(|&&(n, _)| n.eq_ignore_ascii_case(name)).map(|&(_, v)| unfold(v));
+    let mut combined = values.next()?;
+    for value in values {
+        let combined = combined.to_mut();
+        combined.extend_from_slice(b", ");
+        combined.extend_from_slice(&value);
+    }
+    Some(combined)
+}
+
+/// Replace each fold in a value (the line ending, and the whitespace on either side of it) with a single space, in
+/// the same way as [`ObsFoldPolicy::Replace`] does when parsing into owned values
+fn unfold(value: &[u8]) -> Cow<'_, [u8]> {
+    if !value.contains(&b'\r') {
+        return Cow::Borrowed(value);
+    }
+
+    let mut unfolded = Vec::with_capacity(value.len());
+    let mut it = value.iter().peekable();
+    while let Some(&b) = it.next() {
+        if b == b'\r' {
+            while unfolded.last() == Some(&b' ') || unfolded.last() == Some(&b'\t') {
+                unfolded.pop();
+            }
+            while it.next_if(|&&b| b == b'\n' || b == b' ' || b == b'\t').is_some() {}
+            unfolded.push(b' ');
+        } else {
+            unfolded.push(b);
+        }
+    }
+    Cow::Owned(unfolded)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::request::{Parser, Status};
+
+    #[test]
+    fn test_parse_borrowed() {
+        let buf = b"POST /upload?x=1 HTTP/1.1\r\nHost: example.com\r\nContent-Length: 5\r\n\
+            X-Name: caf\xe9\r\n\r\nhello";
+        let (request, end) = RequestRef::parse(buf, &ParserConfig::default()).unwrap().unwrap();
+
+        assert_eq!(&buf[end..], b"hello");
+        assert_eq!(request.get_method(), "POST");
+        assert_eq!(request.get_target(), "/upload?x=1");
+        assert_eq!(request.get_version(), (1, 1));
+        assert_eq!(request.get_header("content-length"), Some(&b"5"[..]));
+        assert_eq!(request.get_header("x-name"), Some(&b"caf\xe9"[..]));
+        assert_eq!(request.get_headers().len(), 3);
+        assert_eq!(request.get_body_length(), BodyLength::Fixed(5));
+
+        // The values really are slices of the buffer
+        let value = request.get_header("Host").unwrap();
+        assert!(buf.as_ptr_range().contains(&value.as_ptr()));
+
+        // Every prefix is incomplete rather than an error
+        for i in 0..end {
+            assert!(RequestRef::parse(&buf[..i], &ParserConfig::default()).unwrap().is_none(), "for length {}", i);
+        }
+    }
+
+    #[test]
+    fn test_matches_owned_parser() {
+        let fold = ParserConfig {
+            obs_fold: ObsFoldPolicy::Replace,
+            .. ParserConfig::default()
+        };
+        let cases: &[(&str, ParserConfig)] = &[
+            ("GET http://a:81/x/../y?q HTTP/1.1\r\nHost: b\r\nAccept: */*  \r\n\r\n", ParserConfig::default()),
+            ("GET / HTTP/1.0\r\nX-Long: a \r\n \t b\r\n\tc\r\nContent-Length: 1\r\n\r\n", fold),
+            ("POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding:\r\n  chunked\r\n\r\n", fold),
+        ];
+        for &(request, ref config) in cases {
+            let owned = match Parser::with_config(config).parse(request.as_bytes()).unwrap() {
+                Status::Complete(head, _) => head,
+                Status::NeedMore => panic!("Expected {:?} to be complete", request),
+            };
+            let borrowed = RequestRef::parse(request.as_bytes(), config).unwrap().unwrap().0.to_head().unwrap();
+
+            assert_eq!(format!("{:?}", borrowed), format!("{:?}", owned));
+        }
+
+        // Both parsers reject the same requests, with the same response code
+        let rejected = [
+            "GET / HTTP/1.1\r\n\r\n",
+            "GET * HTTP/1.1\r\nHost: a\r\n\r\n",
+            "GET  HTTP/1.1\r\nHost: a\r\n\r\n",
+            "CONNECT example.com HTTP/1.1\r\nHost: a\r\n\r\n",
+            "GET /a%zz HTTP/1.1\r\nHost: a\r\n\r\n",
+            "GET / HTTP/1.1\r\nHost: a b\r\n\r\n",
+            "GET / HTTP/1.1\r\nHost: a\r\nExpect: foo\r\n\r\n",
+        ];
+        for request in &rejected {
+            let owned = Parser::new().parse(request.as_bytes()).err().and_then(|e| e.http_response_code());
+            let borrowed = RequestRef::parse(request.as_bytes(), &ParserConfig::default()).err()
+                .and_then(|e| e.http_response_code());
+            assert!(owned.is_some(), "Expected {:?} to be rejected", request);
+            assert_eq!(borrowed, owned, "for {:?}", request);
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        let config = ParserConfig::default();
+        let cases = [
+            ("GET / HTTP/2.0\r\n\r\n", Some(505)),
+            ("GET / HTTP/1.1\r\nHost: a\r\nX: y\r\n z\r\n\r\n", Some(400)),
+            ("GET / HTTP/1.1\r\nHost: a\r\nContent-Length: 1\r\nContent-Length: 2\r\n\r\n", Some(400)),
+            ("GET / HTTP/1.1\r\nHo st: a\r\n\r\n", Some(400)),
+            ("GET\x01", Some(400)),
+            (" / HTTP/1.1\r\nHost: a\r\n\r\n", Some(400)),
+            ("GET / HTTP/1.1\r\n\r\n", Some(400)),
+            ("GET * HTTP/1.1\r\nHost: a\r\n\r\n", Some(400)),
+            ("GET  HTTP/1.1\r\nHost: a\r\n\r\n", Some(400)),
+            ("GET /%00 HTTP/1.1\r\nHost: a\r\n\r\n", Some(400)),
+            ("GET / HTTP/1.1\r\nHost: a b\r\n\r\n", Some(400)),
+            ("GET / HTTP/1.1\r\nHost: a\r\nHost: b\r\n\r\n", Some(400)),
+            ("GET / HTTP/1.1\r\nHost: a\r\nExpect: foo\r\n\r\n", Some(417)),
+            ("GET / HTTP/1.0\r\nExpect: foo\r\n\r\n", None),
+            ("OPTIONS * HTTP/1.1\r\nHost: a\r\n\r\n", None),
+        ];
+        for &(request, expected) in &cases {
+            let result = RequestRef::parse(request.as_bytes(), &config);
+            let status = result.err().and_then(|e| e.http_response_code()).map(u16::from);
+            assert_eq!(status, expected, "for {:?}", request);
+        }
+    }
+
+    #[test]
+    fn test_header_size_limit() {
+        let config = ParserConfig {
+            max_header_size: 20,
+            .. ParserConfig::default()
+        };
+        let cases = [
+            ("GET / HTTP/1.0\r\nA: 12345678901234567\r\n\r\n", None),
+            ("GET / HTTP/1.0\r\nA: 12345678901234567\r\nB: 1\r\n\r\n", None),
+            ("GET / HTTP/1.0\r\nA: 123456789012345678\r\n\r\n", Some(431)),
+        ];
+        for &(request, expected) in &cases {
+            let result = RequestRef::parse(request.as_bytes(), &config);
+            let status = result.err().and_then(|e| e.http_response_code()).map(u16::from);
+            assert_eq!(status, expected, "for {:?}", request);
+        }
+    }
+}
@@ -0,0 +1,483 @@
+//! Parsing of the request target, in each of the forms defined in
+//! [RFC 7230 §5.3](https://tools.ietf.org/html/rfc7230#section-5.3)
+
+use std::fmt;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use super::Method;
+use super::util::{ParseError, percent_decode};
+
+/// The form that a request target was sent in
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum TargetForm {
+    /// An absolute path, optionally followed by a query, such as `/where?q=now`. This is the form used for most
+    /// requests.
+    Origin,
+    /// An absolute URI, such as `http://www.example.org/pub/WWW/TheProject.html`, which is used for requests to
+    /// proxies
+    Absolute,
+    /// Just the authority (host and port), such as `www.example.com:80`, which is only used for `CONNECT` requests
+    Authority,
+    /// A single asterisk, which is only used for server-wide `OPTIONS` requests
+    Asterisk,
+}
+
+/// The host component of an authority, as defined in [RFC 3986 §3.2.2](https://tools.ietf.org/html/rfc3986#section-3.2.2)
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum Host {
+    /// A registered name, such as `www.example.com`. Names are case-insensitive, so they are stored in lower case.
+    Name(String),
+    /// An IPv4 address, such as `127.0.0.1`
+    Ipv4(Ipv4Addr),
+    /// An IPv6 address, which is written in brackets, such as `[::1]`
+    Ipv6(Ipv6Addr),
+}
+
+impl fmt::Display for Host {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Host::Name(ref name) => write!(f, "{}", name),
+            Host::Ipv4(ref addr) => write!(f, "{}", addr),
+            Host::Ipv6(ref addr) => write!(f, "[{}]", addr),
+        }
+    }
+}
+
+/// The authority component of a URI, which is a host and an optional port, as defined in
+/// [RFC 3986 §3.2](https://tools.ietf.org/html/rfc3986#section-3.2).
+///
+/// The `userinfo` subcomponent is deprecated for HTTP, and
+/// [RFC 7230 §2.7.1](https://tools.ietf.org/html/rfc7230#section-2.7.1) says that it should be treated as an error, so
+/// it isn't supported.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Authority {
+    host: Host,
+    port: Option<u16>,
+}
+
+impl Authority {
+    /// Parse an authority, in the form `host[:port]`
+    pub fn parse(authority: &str) -> Result<Authority, ParseError> {
+        if authority.contains('@') {
+            return Err(ParseError::InvalidRequestTarget);
+        }
+
+        // Split the port off. IPv6 addresses contain colons, so they need to be handled separately.
+        let (host, port) = if authority.starts_with('[') {
+            let end = authority.find(']').ok_or(ParseError::InvalidRequestTarget)?;
+            let port = match &authority[end + 1..] {
+                "" => None,
+                rest if rest.starts_with(':') => Some(&rest[1..]),
+                _ => return Err(ParseError::InvalidRequestTarget),
+            };
+            (&authority[..end + 1], port)
+        } else {
+            match authority.rfind(':') {
+                Some(i) => (&authority[..i], Some(&authority[i + 1..])),
+                None => (authority, None),
+            }
+        };
+
+        let host = if host.starts_with('[') {
+            // IPvFuture isn't supported, because there's no way to use it
+            match host[1..host.len() - 1].parse() {
+                Ok(addr) => Host::Ipv6(addr),
+                Err(_) => return Err(ParseError::InvalidRequestTarget),
+            }
+        } else {
+            // HTTP URIs must not have an empty host (RFC 7230 §2.7.1)
+            if host.is_empty() || !host.bytes().all(is_reg_name_char) {
+                return Err(ParseError::InvalidRequestTarget);
+            }
+            match host.parse() {
+                Ok(addr) => Host::Ipv4(addr),
+                Err(_) => Host::Name(host.to_ascii_lowercase()),
+            }
+        };
+
+        // The port may be empty, which is equivalent to omitting it
+        let port = match port {
+            Some(p) if !p.is_empty() => {
+                if !p.bytes().all(|b| b.is_ascii_digit()) {
+                    return Err(ParseError::InvalidRequestTarget);
+                }
+                Some(p.parse().map_err(|_| ParseError::InvalidRequestTarget)?)
+            },
+            _ => None,
+        };
+
+        Ok(Authority {
+            host,
+            port,
+        })
+    }
+
+    /// Get the host
+    pub fn get_host(&self) -> &Host {
+        &self.host
+    }
+
+    /// Get the port, if one was specified
+    pub fn get_port(&self) -> Option<u16> {
+        self.port
+    }
+}
+
+impl fmt::Display for Authority {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.port {
+            Some(port) => write!(f, "{}:{}", self.host, port),
+            None => write!(f, "{}", self.host),
+        }
+    }
+}
+
+/// The target of a request, split into its components
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct RequestTarget {
+    /// The target, exactly as it was received
+    raw: String,
+    form: TargetForm,
+    scheme: Option<String>,
+    authority: Option<Authority>,
+    /// The path, exactly as it was received
+    raw_path: String,
+    /// The path after percent-decoding and removing dot segments
+    path: String,
+    query: Option<String>,
+}
+
+/// The components of a request target, borrowed from it, before the path has been decoded
+struct TargetParts<'a> {
+    form: TargetForm,
+    scheme: Option<&'a str>,
+    authority: Option<Authority>,
+    /// The path exactly as it was received, which is empty for the authority and asterisk forms
+    raw_path: &'a str,
+    query: Option<&'a str>,
+}
+
+impl<'a> TargetParts<'a> {
+    /// Split a request target into its components, and check everything apart from the encoding of the path
+    fn split(method: &Method, raw: &'a str) -> Result<TargetParts<'a>, ParseError> {
+        // Fragments are only used by the client, so they must never be sent
+        if raw.is_empty() || raw.contains('#') {
+            return Err(ParseError::InvalidRequestTarget);
+        }
+
+        let mut parts = TargetParts {
+            form: TargetForm::Origin,
+            scheme: None,
+            authority: None,
+            raw_path: "",
+            query: None,
+        };
+
+        let path_and_query = if *method == Method::Connect {
+            // The port is required for CONNECT requests (RFC 7231 §4.3.6)
+            let authority = Authority::parse(raw)?;
+            if authority.get_port().is_none() {
+                return Err(ParseError::InvalidRequestTarget);
+            }
+            parts.form = TargetForm::Authority;
+            parts.authority = Some(authority);
+            ""
+        } else if raw == "*" {
+            if *method != Method::Options {
+                return Err(ParseError::InvalidRequestTarget);
+            }
+            parts.form = TargetForm::Asterisk;
+            ""
+        } else if raw.starts_with('/') {
+            raw
+        } else {
+            parts.form = TargetForm::Absolute;
+
+            let colon = raw.find(':').ok_or(ParseError::InvalidRequestTarget)?;
+            let scheme = &raw[..colon];
+            if !is_scheme(scheme) {
+                return Err(ParseError::InvalidRequestTarget);
+            }
+            parts.scheme = Some(scheme);
+
+            // HTTP URIs always have an authority, so URIs without one can't identify a resource on this server
+            let rest = raw[colon + 1..].strip_prefix("//").ok_or(ParseError::InvalidRequestTarget)?;
+            let end = rest.find(['/', '?']).unwrap_or(rest.len());
+            parts.authority = Some(Authority::parse(&rest[..end])?);
+            &rest[end..]
+        };
+
+        match path_and_query.find('?') {
+            Some(i) => {
+                parts.raw_path = &path_and_query[..i];
+                parts.query = Some(&path_and_query[i + 1..]);
+            },
+            None => parts.raw_path = path_and_query,
+        }
+        // An empty path is equivalent to `/` (RFC 3986 §6.2.3)
+        if parts.form == TargetForm::Absolute && parts.raw_path.is_empty() {
+            parts.raw_path = "/";
+        }
+        Ok(parts)
+    }
+
+    /// Percent-decode the path. An encoded NUL is never legitimate in a path, and is dangerous to pass on to the file
+    /// system, so it is rejected.
+    fn decode_path(&self) -> Result<String, ParseError> {
+        let path = percent_decode(self.raw_path)?;
+        if path.contains('\0') {
+            return Err(ParseError::InvalidPercentEncoding);
+        }
+        Ok(path)
+    }
+}
+
+impl RequestTarget {
+    /// Parse a request target, and check that the form it was sent in is permitted for `method`.
+    ///
+    /// `CONNECT` requests must use the authority form, and the asterisk form can only be used for `OPTIONS` requests
+    /// ([RFC 7230 §5.3](https://tools.ietf.org/html/rfc7230#section-5.3)). The characters in `raw` are expected to
+    /// have been checked already.
+    pub fn parse(method: &Method, raw: String) -> Result<RequestTarget, ParseError> {
+        let parts = TargetParts::split(method, &raw)?;
+        let path = if parts.raw_path.is_empty() { String::new() } else { remove_dot_segments(&parts.decode_path()?) };
+
+        Ok(RequestTarget {
+            form: parts.form,
+            scheme: parts.scheme.map(str::to_ascii_lowercase),
+            raw_path: parts.raw_path.to_string(),
+            path,
+            query: parts.query.map(String::from),
+            authority: parts.authority,
+            raw,
+        })
+    }
+
+    /// Check a request target in the same way as [`parse`](#method.parse), without copying it, and return its form
+    /// along with its authority (if it has one). The path is only decoded if it contains percent-encoded characters.
+    pub(crate) fn validate(method: &Method, raw: &str) -> Result<(TargetForm, Option<Authority>), ParseError> {
+        let parts = TargetParts::split(method, raw)?;
+        if parts.raw_path.contains('%') {
+            parts.decode_path()?;
+        }
+        Ok((parts.form, parts.authority))
+    }
+
+    /// Get the target exactly as it was sent
+    pub fn as_str(&self) -> &str {
+        &self.raw
+    }
+
+    /// Get the form that the target was sent in
+    pub fn get_form(&self) -> TargetForm {
+        self.form
+    }
+
+    /// Get the scheme (in lower case), which is only present for absolute form targets
+    pub fn get_scheme(&self) -> Option<&str> {
+        self.scheme.as_deref()
+    }
+
+    /// Get the authority, which is present for authority form and absolute form targets
+    pub fn get_authority(&self) -> Option<&Authority> {
+        self.authority.as_ref()
+    }
+
+    /// Get the path, after decoding any percent-encoded characters and then resolving any `.` and `..` segments. The
+    /// result always starts with `/` and never contains dot segments, so it can't refer to anything outside of the
+    /// root. This is empty for authority and asterisk form targets.
+    pub fn get_path(&self) -> &str {
+        &self.path
+    }
+
+    /// Get the path exactly as it was sent, without any decoding or normalisation. This is empty for authority and
+    /// asterisk form targets.
+    pub fn get_raw_path(&self) -> &str {
+        &self.raw_path
+    }
+
+    /// Get the query (without the leading `?`), if there is one
+    pub fn get_query(&self) -> Option<&str> {
+        self.query.as_deref()
+    }
+}
+
+impl fmt::Display for RequestTarget {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.raw)
+    }
+}
+
+/// Remove the `.` and `..` segments from an absolute path, as described in
+/// [RFC 3986 §5.2.4](https://tools.ietf.org/html/rfc3986#section-5.2.4). `..` segments can't go above the root.
+fn remove_dot_segments(path: &str) -> String {
+    let mut segments = Vec::new();
+    // Whether the output should end in a slash, because the last segment was a dot segment
+    let mut trailing_slash = false;
+    // The path starts with a slash, so the first segment is always empty
+    for segment in path.split('/').skip(1) {
+        match segment {
+            "." => trailing_slash = true,
+            ".." => {
+                segments.pop();
+                trailing_slash = true;
+            },
+            s => {
+                segments.push(s);
+                trailing_slash = false;
+            },
+        }
+    }
+
+    let mut output = String::with_capacity(path.len());
+    for segment in &segments {
+        output.push('/');
+        output.push_str(segment);
+    }
+    if trailing_slash || segments.is_empty() {
+        output.push('/');
+    }
+    output
+}
+
+/// Check whether `scheme` is a valid URI scheme, which is a letter followed by letters, digits, `+`, `-` and `.`
+/// ([RFC 3986 §3.1](https://tools.ietf.org/html/rfc3986#section-3.1))
+fn is_scheme(scheme: &str) -> bool {
+    let mut bytes = scheme.bytes();
+    match bytes.next() {
+        Some(b) if b.is_ascii_alphabetic() => (),
+        _ => return false,
+    }
+    bytes.all(|b| b.is_ascii_alphanumeric() || b == b'+' || b == b'-' || b == b'.')
+}
+
+/// Check whether `b` can be used in a registered name, which consists of unreserved characters, sub-delimiters and
+/// percent-encoded characters ([RFC 3986 §3.2.2](https://tools.ietf.org/html/rfc3986#section-3.2.2))
+fn is_reg_name_char(b: u8) -> bool {
+    matches!(b, b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'%' |
+        b'!' | b'$' | b'&' | b'\'' | b'(' | b')' | b'*' | b'+' | b',' | b';' | b'=')
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(method: Method, raw: &str) -> Result<RequestTarget, ParseError> {
+        RequestTarget::parse(&method, String::from(raw))
+    }
+
+    #[test]
+    fn test_origin_form() {
+        let target = parse(Method::Get, "/where?q=now&x=?").unwrap();
+        assert_eq!(target.get_form(), TargetForm::Origin);
+        assert_eq!(target.get_scheme(), None);
+        assert_eq!(target.get_authority(), None);
+        assert_eq!(target.get_path(), "/where");
+        assert_eq!(target.get_query(), Some("q=now&x=?"));
+        assert_eq!(target.as_str(), "/where?q=now&x=?");
+
+        let target = parse(Method::Get, "/").unwrap();
+        assert_eq!(target.get_path(), "/");
+        assert_eq!(target.get_query(), None);
+    }
+
+    #[test]
+    fn test_absolute_form() {
+        let target = parse(Method::Get, "HTTP://WWW.Example.org:8080/pub/WWW/TheProject.html?a").unwrap();
+        assert_eq!(target.get_form(), TargetForm::Absolute);
+        assert_eq!(target.get_scheme(), Some("http"));
+        let authority = target.get_authority().unwrap();
+        assert_eq!(authority.get_host(), &Host::Name(String::from("www.example.org")));
+        assert_eq!(authority.get_port(), Some(8080));
+        assert_eq!(target.get_path(), "/pub/WWW/TheProject.html");
+        assert_eq!(target.get_query(), Some("a"));
+
+        let target = parse(Method::Options, "http://[::1]?q").unwrap();
+        assert_eq!(target.get_authority().unwrap().get_host(), &Host::Ipv6("::1".parse().unwrap()));
+        assert_eq!(target.get_authority().unwrap().get_port(), None);
+        assert_eq!(target.get_path(), "/");
+        assert_eq!(target.get_query(), Some("q"));
+    }
+
+    #[test]
+    fn test_authority_form() {
+        let target = parse(Method::Connect, "www.example.com:443").unwrap();
+        assert_eq!(target.get_form(), TargetForm::Authority);
+        assert_eq!(target.get_authority().unwrap().to_string(), "www.example.com:443");
+        assert_eq!(target.get_path(), "");
+
+        let target = parse(Method::Connect, "[2001:db8::7]:80").unwrap();
+        assert_eq!(target.get_authority().unwrap().get_host(), &Host::Ipv6("2001:db8::7".parse().unwrap()));
+        assert_eq!(target.get_authority().unwrap().to_string(), "[2001:db8::7]:80");
+
+        let target = parse(Method::Connect, "127.0.0.1:8080").unwrap();
+        assert_eq!(target.get_authority().unwrap().get_host(), &Host::Ipv4(Ipv4Addr::new(127, 0, 0, 1)));
+    }
+
+    #[test]
+    fn test_asterisk_form() {
+        let target = parse(Method::Options, "*").unwrap();
+        assert_eq!(target.get_form(), TargetForm::Asterisk);
+        assert_eq!(target.get_path(), "");
+    }
+
+    #[test]
+    fn test_path_normalisation() {
+        let cases = [
+            ("/", "/"),
+            ("/a/b/c", "/a/b/c"),
+            ("/a/b/", "/a/b/"),
+            ("/a/./b/../c", "/a/c"),
+            ("/a/b/..", "/a/"),
+            ("/a/.", "/a/"),
+            ("/..", "/"),
+            ("/../../etc/passwd", "/etc/passwd"),
+            ("/a/%2e%2E/%2E/b", "/b"),
+            ("/a%2F..%2F..%2Fsecret", "/secret"),
+            ("/a//b", "/a//b"),
+            ("/caf%C3%A9?x=%ZZ", "/café"),
+        ];
+        for &(raw, normalised) in &cases {
+            let target = parse(Method::Get, raw).unwrap();
+            assert_eq!(target.get_path(), normalised, "for {}", raw);
+            assert_eq!(target.get_raw_path(), raw.split('?').next().unwrap());
+        }
+
+        let target = parse(Method::Get, "http://example.com/../a").unwrap();
+        assert_eq!(target.get_path(), "/a");
+
+        for raw in &["/%", "/%zz", "/a%2", "/%FF", "/a%00b"] {
+            match parse(Method::Get, raw) {
+                Err(ParseError::InvalidPercentEncoding) => (),
+                r => panic!("Expected InvalidPercentEncoding for {}, got {:?}", raw, r),
+            }
+        }
+    }
+
+    #[test]
+    fn test_invalid_targets() {
+        let cases = [
+            (Method::Get, "*"),
+            (Method::Get, "www.example.com:80"),
+            (Method::Get, "1http://example.com/"),
+            (Method::Get, "urn:isbn:0451450523"),
+            (Method::Get, "/path#fragment"),
+            (Method::Get, "http://user@example.com/"),
+            (Method::Get, "http:///path"),
+            (Method::Get, "http://example.com:99999/"),
+            (Method::Get, "http://[::1/"),
+            (Method::Get, "http://[not-ip]/"),
+            (Method::Connect, "/path"),
+            (Method::Connect, "*"),
+            (Method::Connect, "example.com:http"),
+            (Method::Connect, "example.com"),
+        ];
+        for &(ref method, raw) in &cases {
+            match parse(method.clone(), raw) {
+                Err(ParseError::InvalidRequestTarget) => (),
+                r => panic!("Expected InvalidRequestTarget for {:?} {}, got {:?}", method, raw, r),
+            }
+        }
+    }
+}
@@ -1,36 +1,131 @@
 //! A private utility module for request parsing
 
 use std::io::prelude::*;
+use std::io;
+use std::cmp;
 use std::error::Error;
 use std::fmt;
+use std::ops::{Deref, DerefMut};
+
+use http::status::StatusCode;
+
+/// The size of the buffer that is used when reading from a stream
+pub const BUFFER_SIZE: usize = 1024;
+
+/// Bytes that have been read from a stream but not consumed yet.
+///
+/// This is separate from [`StreamReader`] so that it can outlive a single request: a client that pipelines requests
+/// may send the next one in the same packet as the end of the previous one, and those bytes mustn't be lost.
+pub struct ReadBuffer {
+    data: [u8; BUFFER_SIZE],
+    index: usize,
+    read: usize,
+    /// Set while the body of a request is being read from the buffer, and cleared once all of it has been read. The
+    /// next request can't be parsed until then, because it starts after the body.
+    body_pending: bool,
+}
+
+impl ReadBuffer {
+    /// Create a new, empty, `ReadBuffer`
+    pub fn new() -> ReadBuffer {
+        ReadBuffer {
+            data: [0; BUFFER_SIZE],
+            index: 0,
+            read: 0,
+            body_pending: false,
+        }
+    }
+
+    /// Get the bytes that have been read but not consumed
+    pub fn available(&self) -> &[u8] {
+        &self.data[self.index..self.read]
+    }
+
+    /// Mark the first `n` available bytes as consumed
+    pub fn consume(&mut self, n: usize) {
+        self.index = cmp::min(self.index + n, self.read);
+    }
+
+    /// Returns `true` if there are no bytes available
+    pub fn is_empty(&self) -> bool {
+        self.index >= self.read
+    }
+
+    /// Replace the contents of the buffer with the next bytes from `stream`, returning the number of bytes read. This
+    /// should only be called once everything available has been consumed.
+    pub fn fill<T: Read + ?Sized>(&mut self, stream: &mut T) -> io::Result<usize> {
+        let n = stream.read(&mut self.data)?;
+        self.index = 0;
+        self.read = n;
+        Ok(n)
+    }
+
+    /// Returns `true` if a request body is still being read
+    pub fn is_body_pending(&self) -> bool {
+        self.body_pending
+    }
+
+    /// Set whether a request body is still being read
+    pub fn set_body_pending(&mut self, pending: bool) {
+        self.body_pending = pending;
+    }
+}
+
+/// A [`ReadBuffer`] that is either owned by a [`StreamReader`], or borrowed from a longer-lived connection
+enum BufferRef<'a> {
+    Owned(Box<ReadBuffer>),
+    Borrowed(&'a mut ReadBuffer),
+}
+
+impl<'a> Deref for BufferRef<'a> {
+    type Target = ReadBuffer;
+
+    fn deref(&self) -> &ReadBuffer {
+        match *self {
+            BufferRef::Owned(ref buffer) => buffer,
+            BufferRef::Borrowed(ref buffer) => buffer,
+        }
+    }
+}
+
+impl<'a> DerefMut for BufferRef<'a> {
+    fn deref_mut(&mut self) -> &mut ReadBuffer {
+        match *self {
+            BufferRef::Owned(ref mut buffer) => buffer,
+            BufferRef::Borrowed(ref mut buffer) => buffer,
+        }
+    }
+}
 
 /// An iterator that wraps around a borrowed struct that implements [`std::io::Read`]. This is needed because the
 /// default iterators in `std::io` take ownership of the reader, but we want to be able to write to it later too.
-pub struct StreamReader<'a, T: Read + 'a> {
+pub struct StreamReader<'a, T: Read + ?Sized + 'a> {
     stream: &'a mut T,
-    buffer: [u8; 1024],
-    index: usize,
-    read: usize,
+    buffer: BufferRef<'a>,
 }
 
-impl<'a, T: Read + 'a> StreamReader<'a, T> {
+impl<'a, T: Read + ?Sized + 'a> StreamReader<'a, T> {
     /// Create a new `StreamReader` from a reader
     pub fn from(stream: &'a mut T) -> StreamReader<'a, T> {
+        StreamReader::from_buffer(stream, ReadBuffer::new())
+    }
+
+    /// Create a new `StreamReader` from a reader, which will yield the bytes in `buffer` (which have already been
+    /// read from the stream) before reading any more
+    pub fn from_buffer(stream: &'a mut T, buffer: ReadBuffer) -> StreamReader<'a, T> {
         StreamReader {
-            stream: stream,
-            buffer: [0; 1024],
-            index: 0,
-            read: 0,
+            stream,
+            buffer: BufferRef::Owned(Box::new(buffer)),
         }
     }
 
-    fn read(&mut self) -> bool {
-        self.read = match self.stream.read(&mut self.buffer) {
-            Ok(n) => n,
-            Err(_) => return false,
-        };
-        self.index = 0;
-        true
+    /// Create a new `StreamReader` that reads through a borrowed buffer, so that any bytes that it reads ahead are
+    /// still available in the buffer once it has been dropped
+    pub fn with_buffer(stream: &'a mut T, buffer: &'a mut ReadBuffer) -> StreamReader<'a, T> {
+        StreamReader {
+            stream,
+            buffer: BufferRef::Borrowed(buffer),
+        }
     }
 
     /// Decrement the iterator, so the next call to `next` will return the previous value again. Returns `true` if the
@@ -40,53 +135,61 @@ impl<'a, T: Read + 'a> StreamReader<'a, T> {
     /// Further invocations may succeed, depending on the current state of the buffer.
     /// 
     /// # Examples
-    /// ```
+    /// ```ignore
     /// let before = reader.next();
     /// reader.step_back().unwrap();
     /// let after = reader.next();
     /// assert_eq!(before, after);
     /// ```
     /// The following example may fail
-    /// ```
+    /// ```ignore
     /// let before = reader.next();
     /// reader.step_back().unwrap(); // fine - we just called next
     /// reader.step_back().unwrap(); // may fail depending on the internal state of reader
     /// ```
     pub fn step_back(&mut self) -> Option<()> {
-        if self.index > 0 {
-            self.index -= 1;
+        if self.buffer.index > 0 {
+            self.buffer.index -= 1;
             Some(())
         } else {
             None
         }
     }
 
-    /// Get the raw reader that this is wrapped around. This invalidates the cached data held by this struct.
-    pub fn get_inner(&mut self) -> &mut T {
-        self.read = 0;
+    /// Get the buffer that this reads through
+    pub fn get_buffer(&mut self) -> &mut ReadBuffer {
+        &mut self.buffer
+    }
+}
 
-        &mut self.stream
+impl<'a, T: Read + ?Sized + 'a> Read for StreamReader<'a, T> {
+    /// Read into `buf`, taking any bytes that have already been buffered first and only then reading from the inner
+    /// stream. This never reads more from the stream than `buf` can hold, so nothing is read ahead.
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.buffer.is_empty() {
+            return self.stream.read(buf);
+        }
+        let available = self.buffer.available();
+        let count = cmp::min(buf.len(), available.len());
+        buf[..count].copy_from_slice(&available[..count]);
+        self.buffer.consume(count);
+        Ok(count)
     }
 }
 
-impl<'a, T: Read + 'a> Iterator for StreamReader<'a, T> {
+impl<'a, T: Read + ?Sized + 'a> Iterator for StreamReader<'a, T> {
     type Item = u8;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.index >= self.read {
-            if !self.read() {
-                return None;
-            }
-            // If we didn't read anything this time, we're done
-            if self.index >= self.read {
-                return None;
-            }
+        // If we didn't read anything this time, we're done
+        if self.buffer.is_empty() && self.buffer.fill(self.stream).unwrap_or(0) == 0 {
+            return None;
         }
 
-        let result = Some(self.buffer[self.index]);
-        self.index += 1;
+        let result = self.buffer.available()[0];
+        self.buffer.consume(1);
 
-        result
+        Some(result)
     }
 }
 
@@ -97,17 +200,35 @@ impl<'a, T: Read + 'a> Iterator for StreamReader<'a, T> {
 pub enum ParseError {
     EOF,
     IllegalCharacter,
+    ObsFold,
+    InvalidRequestTarget,
+    InvalidPercentEncoding,
     MissingRequiredHeader (&'static str),
-    ServerError (Box<Error>),
-    Generic {err: Box<Error>, http_response: u16},
+    InvalidContentLength,
+    InvalidTransferEncoding,
+    UnsupportedTransferCoding,
+    ConflictingFraming,
+    InvalidChunkSize,
+    InvalidChunkExtension,
+    ChunkNotTerminated,
+    MethodTooLong,
+    TargetTooLong,
+    HeaderTooLarge,
+    TooManyHeaders,
+    HeaderSectionTooLarge,
+    BodyTooLarge,
+    ExpectationFailed,
+    UnsupportedVersion,
+    ServerError (Box<dyn Error + Send + Sync>),
+    Generic {err: Box<dyn Error + Send + Sync>, http_response: StatusCode},
 }
 
 impl ParseError {
     /// Create a new generic error from anything that can be converted into an error (including &str).
     ///
     /// The HTTP response code that should be sent also needs to be provided
-    pub fn new_generic<E>(err: E, http_response: u16) -> ParseError
-        where E: Into<Box<Error>>
+    pub fn new_generic<E>(err: E, http_response: StatusCode) -> ParseError
+        where E: Into<Box<dyn Error + Send + Sync>>
     {
         ParseError::Generic {
             err: err.into(),
@@ -118,14 +239,14 @@ impl ParseError {
     /// Create a new generic error from anything that can be converted into an error (including &str), and return error
     /// 400 Bad Request to the client
     pub fn new_bad_request<E>(err: E) -> ParseError
-        where E: Into<Box<Error>>
+        where E: Into<Box<dyn Error + Send + Sync>>
     {
-        ParseError::new_generic(err, 400)
+        ParseError::new_generic(err, StatusCode::BAD_REQUEST)
     }
 
     /// Create a new server error from an existing error, and return 500 Internal Server Error to the client
     pub fn new_server_error<E>(err: E) -> ParseError
-        where E: Into<Box<Error>>
+        where E: Into<Box<dyn Error + Send + Sync>>
     {
         ParseError::ServerError(err.into())
     }
@@ -133,37 +254,70 @@ impl ParseError {
     /// Get the HTTP response code that should be sent to the client.
     /// 
     /// Returns None if the connection should be closed with no response sent.
-    pub fn http_response_code(&self) -> Option<u16> {
-        match self {
-            &ParseError::EOF => None,
-            &ParseError::IllegalCharacter => Some(400),
-            &ParseError::MissingRequiredHeader (_) => Some(400),
-            &ParseError::ServerError(_) => Some(500),
-            &ParseError::Generic {http_response: r, ..} => Some(r),
+    pub fn http_response_code(&self) -> Option<StatusCode> {
+        match *self {
+            ParseError::EOF => None,
+            ParseError::IllegalCharacter => Some(StatusCode::BAD_REQUEST),
+            ParseError::ObsFold => Some(StatusCode::BAD_REQUEST),
+            ParseError::InvalidRequestTarget => Some(StatusCode::BAD_REQUEST),
+            ParseError::InvalidPercentEncoding => Some(StatusCode::BAD_REQUEST),
+            ParseError::MissingRequiredHeader (_) => Some(StatusCode::BAD_REQUEST),
+            ParseError::InvalidContentLength => Some(StatusCode::BAD_REQUEST),
+            ParseError::InvalidTransferEncoding => Some(StatusCode::BAD_REQUEST),
+            ParseError::UnsupportedTransferCoding => Some(StatusCode::NOT_IMPLEMENTED),
+            ParseError::ConflictingFraming => Some(StatusCode::BAD_REQUEST),
+            ParseError::InvalidChunkSize => Some(StatusCode::BAD_REQUEST),
+            ParseError::InvalidChunkExtension => Some(StatusCode::BAD_REQUEST),
+            ParseError::ChunkNotTerminated => Some(StatusCode::BAD_REQUEST),
+            ParseError::MethodTooLong => Some(StatusCode::NOT_IMPLEMENTED),
+            ParseError::TargetTooLong => Some(StatusCode::URI_TOO_LONG),
+            ParseError::HeaderTooLarge => Some(StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE),
+            ParseError::TooManyHeaders => Some(StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE),
+            ParseError::HeaderSectionTooLarge => Some(StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE),
+            ParseError::BodyTooLarge => Some(StatusCode::PAYLOAD_TOO_LARGE),
+            ParseError::ExpectationFailed => Some(StatusCode::EXPECTATION_FAILED),
+            ParseError::UnsupportedVersion => Some(StatusCode::HTTP_VERSION_NOT_SUPPORTED),
+            ParseError::ServerError(_) => Some(StatusCode::INTERNAL_SERVER_ERROR),
+            ParseError::Generic {http_response: r, ..} => Some(r),
         }
     }
 }
 
 impl fmt::Display for ParseError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ParseError::EOF => write!(f, "End of file reached while parsing headers")?,
+            ParseError::IllegalCharacter => write!(f, "Illegal character encountered while parsing headers")?,
+            ParseError::ObsFold => write!(f, "Header field split across multiple lines (obsolete line folding)")?,
+            ParseError::InvalidRequestTarget => write!(f, "Invalid request target")?,
+            ParseError::InvalidPercentEncoding => write!(f, "Invalid percent-encoding in request target")?,
+            ParseError::MissingRequiredHeader (h) => write!(f, "Missing required header: {}", h)?,
+            ParseError::InvalidContentLength => write!(f, "Invalid Content-Length header")?,
+            ParseError::InvalidTransferEncoding => write!(f, "Invalid Transfer-Encoding header")?,
+            ParseError::UnsupportedTransferCoding => write!(f, "Unsupported transfer coding")?,
+            ParseError::ConflictingFraming => write!(f, "Conflicting Content-Length or Transfer-Encoding headers")?,
+            ParseError::InvalidChunkSize => write!(f, "Invalid chunk size in chunked body")?,
+            ParseError::InvalidChunkExtension => write!(f, "Invalid chunk extension in chunked body")?,
+            ParseError::ChunkNotTerminated => write!(f, "Chunk in chunked body not terminated by CRLF")?,
+            ParseError::MethodTooLong => write!(f, "Request method too long")?,
+            ParseError::TargetTooLong => write!(f, "Request target too long")?,
+            ParseError::HeaderTooLarge => write!(f, "Header field too large")?,
+            ParseError::TooManyHeaders => write!(f, "Too many header fields")?,
+            ParseError::HeaderSectionTooLarge => write!(f, "Header fields too large in total")?,
+            ParseError::BodyTooLarge => write!(f, "Request body too large")?,
+            ParseError::ExpectationFailed => write!(f, "Unsupported expectation in Expect header")?,
+            ParseError::UnsupportedVersion => write!(f, "HTTP version not supported")?,
+            ParseError::ServerError(ref e) => write!(f, "{}", e)?,
+            ParseError::Generic {ref err, ..} => write!(f, "{}", err)?,
+        }
         match self.http_response_code() {
-            Some(c) => write!(f, "{} (HTTP {})", self.description(), c),
-            None => write!(f, "{} (no response sent to client)", self.description()),
+            Some(c) => write!(f, " (HTTP {})", c.as_u16()),
+            None => write!(f, " (no response sent to client)"),
         }
     }
 }
 
-impl Error for ParseError {
-    fn description(&self) -> &str {
-        match self {
-            &ParseError::EOF => "End of file reached while parsing headers",
-            &ParseError::IllegalCharacter => "Illegal character encountered while parsing headers",
-            &ParseError::MissingRequiredHeader (_h) => "Missing required header",
-            &ParseError::ServerError(ref e) => e.description(),
-            &ParseError::Generic {ref err, ..} => err.description(),
-        }
-    }
-}
+impl Error for ParseError {}
 
 
 /// A wrapper for parsing `token` as defined in [RFC 7230 Appendix B](https://tools.ietf.org/html/rfc7230#appendix-B).
@@ -179,13 +333,178 @@ pub enum TokenType {
 
 impl TokenType {
     pub fn from(c: u8) -> TokenType {
-        match c {
-            b'!' | b'#' | b'$' | b'%' | b'&' | b'\'' | b'*' | b'+' | b'-' | b'.' | b'^' | b'_' | b'`' | b'|' | b'~' |
-                b'0'...b'9' | b'a'...b'z' | b'A'...b'Z' => TokenType::TChar(c),
-            c => TokenType::Invalid(c),
+        if TCHAR[c as usize] {
+            TokenType::TChar(c)
+        } else {
+            TokenType::Invalid(c)
         }
     }
 }
 
 unsafe impl Send for TokenType {}
 unsafe impl Sync for TokenType {}
+
+
+/// A lookup table, indexed by byte, of whether each byte is allowed in some part of a request. Checking a table is
+/// much cheaper than matching against a list of ranges, which matters when validating long runs of bytes.
+pub type ByteTable = [bool; 256];
+
+/// Build a [`ByteTable`] that allows each byte in `bytes`, and every byte in each of the inclusive `ranges`
+const fn byte_table(bytes: &[u8], ranges: &[(u8, u8)]) -> ByteTable {
+    let mut table = [false; 256];
+    let mut i = 0;
+    while i < bytes.len() {
+        table[bytes[i] as usize] = true;
+        i += 1;
+    }
+    let mut i = 0;
+    while i < ranges.len() {
+        let mut b = ranges[i].0 as usize;
+        while b <= ranges[i].1 as usize {
+            table[b] = true;
+            b += 1;
+        }
+        i += 1;
+    }
+    table
+}
+
+/// `tchar`, which makes up methods and field names
+pub static TCHAR: ByteTable = byte_table(b"!#$%&'*+-.^_`|~", &[(b'0', b'9'), (b'a', b'z'), (b'A', b'Z')]);
+
+/// The characters that are allowed in the request target, per [RFC 3986](https://tools.ietf.org/html/rfc3986#appendix-A)
+pub static TARGET_CHAR: ByteTable = byte_table(b"!=|~", &[(b'#', b';'), (b'?', b'['), (b']', b'z')]);
+
+/// The characters that are allowed in a field value (`field-vchar`, `obs-text` and whitespace)
+pub static FIELD_CHAR: ByteTable = byte_table(b"\t", &[(b' ', b'~'), (0x80, 0xFF)]);
+
+/// Find the length of the run of bytes at the start of `data` that are all allowed by `table`
+pub fn scan_while(data: &[u8], table: &ByteTable) -> usize {
+    data.iter().position(|&b| !table[b as usize]).unwrap_or(data.len())
+}
+
+/// Find the length of the run of bytes at the start of `data` that are all allowed by `table`, when the run is
+/// expected to end with `delimiter`. The delimiter is found a word at a time with [`find_byte`], and only then is
+/// everything before it validated, which is faster than [`scan_while`] for long runs such as field values.
+pub fn scan_until(data: &[u8], delimiter: u8, table: &ByteTable) -> usize {
+    let end = find_byte(delimiter, data).unwrap_or(data.len());
+    if data[..end].iter().all(|&b| table[b as usize]) {
+        end
+    } else {
+        scan_while(&data[..end], table)
+    }
+}
+
+/// Find the index of the first occurrence of `needle` in `haystack`.
+///
+/// Like `memchr`, this checks eight bytes at a time, using the fact that `x - 0x01` has its high bit set in a byte
+/// where `x` is zero (and `x` doesn't). Each word is XORed with `needle` in every byte, so that matching bytes become
+/// zero. This works on every CPU, without needing SIMD instructions.
+pub fn find_byte(needle: u8, haystack: &[u8]) -> Option<usize> {
+    const LOW_BITS: u64 = 0x0101_0101_0101_0101;
+    const HIGH_BITS: u64 = 0x8080_8080_8080_8080;
+    let pattern = LOW_BITS * u64::from(needle);
+
+    let mut chunks = haystack.chunks_exact(8);
+    let mut offset = 0;
+    for chunk in &mut chunks {
+        // Little endian order puts the first byte in the lowest bits, so the lowest set bit is the first match. Bytes
+        // above a match can be false positives because of the borrow, but those are never the lowest.
+        let x = u64::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3], chunk[4], chunk[5], chunk[6], chunk[7]])
+            ^ pattern;
+        let found = x.wrapping_sub(LOW_BITS) & !x & HIGH_BITS;
+        if found != 0 {
+            return Some(offset + found.trailing_zeros() as usize / 8);
+        }
+        offset += 8;
+    }
+    chunks.remainder().iter().position(|&b| b == needle).map(|i| offset + i)
+}
+
+/// Get the value of a hexadecimal digit, or `None` if `b` isn't one
+pub fn hex_value(b: u8) -> Option<u8> {
+    match b {
+        b'0'..=b'9' => Some(b - b'0'),
+        b'a'..=b'f' => Some(b - b'a' + 10),
+        b'A'..=b'F' => Some(b - b'A' + 10),
+        _ => None,
+    }
+}
+
+/// Decode the percent-encoded octets in `input`, as defined in
+/// [RFC 3986 §2.1](https://tools.ietf.org/html/rfc3986#section-2.1), and check that the result is valid UTF-8.
+///
+/// A `%` that isn't followed by two hexadecimal digits is an error.
+pub fn percent_decode(input: &str) -> Result<String, ParseError> {
+    let input = input.as_bytes();
+    let mut output = Vec::with_capacity(input.len());
+    let mut i = 0;
+    while i < input.len() {
+        if input[i] == b'%' {
+            let high = input.get(i + 1).and_then(|&b| hex_value(b));
+            let low = input.get(i + 2).and_then(|&b| hex_value(b));
+            match (high, low) {
+                (Some(h), Some(l)) => output.push(h * 16 + l),
+                _ => return Err(ParseError::InvalidPercentEncoding),
+            }
+            i += 3;
+        } else {
+            output.push(input[i]);
+            i += 1;
+        }
+    }
+
+    String::from_utf8(output).map_err(|_| ParseError::InvalidPercentEncoding)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_byte() {
+        let data = b"Accept-Language: en-GB,en;q=0.9\r\n\r\n";
+        for needle in 0..=255 {
+            assert_eq!(find_byte(needle, data), data.iter().position(|&b| b == needle), "for {}", needle);
+        }
+        // Check positions either side of every word boundary, followed by bytes that differ from the needle in one bit
+        for i in 0..data.len() {
+            let mut data = vec![b'x'; data.len() + 2];
+            data[i] = b'\r';
+            data[i + 1] = b'\r' ^ 0x01;
+            data[i + 2] = b'\r' ^ 0x80;
+            assert_eq!(find_byte(b'\r', &data), Some(i));
+        }
+        assert_eq!(find_byte(b':', b""), None);
+    }
+
+    #[test]
+    fn test_tables() {
+        for b in 0..=255u8 {
+            let tchar = matches!(b, b'!' | b'#' | b'$' | b'%' | b'&' | b'\'' | b'*' | b'+' | b'-' | b'.' | b'^' | b'_' |
+                b'`' | b'|' | b'~' | b'0'..=b'9' | b'a'..=b'z' | b'A'..=b'Z');
+            assert_eq!(TCHAR[b as usize], tchar, "for {}", b);
+            let target = matches!(b, b'!' | b'#'..=b';' | b'=' | b'?'..=b'[' | b']'..=b'z' | b'|' | b'~');
+            assert_eq!(TARGET_CHAR[b as usize], target, "for {}", b);
+        }
+        assert_eq!(scan_while(b"Content-Type: text/html", &TCHAR), 12);
+        assert_eq!(scan_until(b"text/html; q=0.5 \r\n", b'\r', &FIELD_CHAR), 17);
+        assert_eq!(scan_until(b"text\x00html\r\n", b'\r', &FIELD_CHAR), 4);
+    }
+
+    #[test]
+    fn test_percent_decode() {
+        assert_eq!(percent_decode("/a%2Fb%2e%2E/%C3%A9t%c3%a9").unwrap(), "/a/b../été");
+        assert_eq!(percent_decode("no escapes").unwrap(), "no escapes");
+
+        assert_eq!(percent_decode("%00").unwrap(), "\0");
+
+        for input in &["%", "%2", "%2g", "%G0", "abc%", "%C3", "%FF"] {
+            match percent_decode(input) {
+                Err(ParseError::InvalidPercentEncoding) => (),
+                r => panic!("Expected InvalidPercentEncoding for {:?}, got {:?}", input, r),
+            }
+        }
+    }
+}
@@ -0,0 +1,140 @@
+//! Response bodies, which are either held in memory or produced while the response is being written
+
+use std::io::prelude::*;
+use std::io;
+use std::fmt;
+use std::sync::mpsc::Receiver;
+
+/// The size of the chunks that are read by [`ResponseBody::from_reader`]
+const READ_CHUNK_SIZE: usize = 8 * 1024;
+
+/// The body of a [`Response`](super::Response).
+///
+/// A body that is already in memory is sent with a `Content-Length`. Streamed bodies are sent as they are produced, so
+/// their length isn't known in advance: they use the chunked transfer coding for HTTP/1.1 clients, and for HTTP/1.0
+/// clients the end of the body is marked by closing the connection.
+pub enum ResponseBody {
+    /// A body that is held in memory
+    Full(Vec<u8>),
+    /// A body that is produced in chunks while it is being written. An error stops the response part of the way
+    /// through, so the connection has to be closed.
+    Stream(Box<dyn Iterator<Item = io::Result<Vec<u8>>> + Send>),
+}
+
+impl ResponseBody {
+    /// Create an empty body
+    pub fn empty() -> ResponseBody {
+        ResponseBody::Full(Vec::new())
+    }
+
+    /// Create a body that is streamed from an iterator, with each item sent as soon as it is produced
+    pub fn from_chunks<I, B>(iter: I) -> ResponseBody
+        where I: IntoIterator<Item = B>, I::IntoIter: Send + 'static, B: Into<Vec<u8>> {
+        ResponseBody::Stream(Box::new(iter.into_iter().map(|chunk| Ok(chunk.into()))))
+    }
+
+    /// Create a body that is streamed from a reader, until it reaches the end of its input
+    pub fn from_reader<R: Read + Send + 'static>(reader: R) -> ResponseBody {
+        ResponseBody::Stream(Box::new(ReaderChunks {
+            reader,
+            done: false,
+        }))
+    }
+
+    /// Create a body that is streamed from a channel, so that it can be produced by another thread. The body ends
+    /// when every sender has been dropped.
+    pub fn from_channel<B: Into<Vec<u8>> + Send + 'static>(receiver: Receiver<B>) -> ResponseBody {
+        ResponseBody::Stream(Box::new(receiver.into_iter().map(|chunk| Ok(chunk.into()))))
+    }
+
+    /// Get the body as bytes, if it is held in memory
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match *self {
+            ResponseBody::Full(ref bytes) => Some(bytes),
+            ResponseBody::Stream(_) => None,
+        }
+    }
+
+    /// Returns `true` if the body is streamed, so its length isn't known in advance
+    pub fn is_stream(&self) -> bool {
+        match *self {
+            ResponseBody::Full(_) => false,
+            ResponseBody::Stream(_) => true,
+        }
+    }
+}
+
+impl Default for ResponseBody {
+    fn default() -> ResponseBody {
+        ResponseBody::empty()
+    }
+}
+
+impl From<Vec<u8>> for ResponseBody {
+    fn from(bytes: Vec<u8>) -> ResponseBody {
+        ResponseBody::Full(bytes)
+    }
+}
+
+impl<'a> From<&'a [u8]> for ResponseBody {
+    fn from(bytes: &'a [u8]) -> ResponseBody {
+        ResponseBody::Full(bytes.to_vec())
+    }
+}
+
+impl From<String> for ResponseBody {
+    fn from(body: String) -> ResponseBody {
+        ResponseBody::Full(body.into_bytes())
+    }
+}
+
+impl<'a> From<&'a str> for ResponseBody {
+    fn from(body: &'a str) -> ResponseBody {
+        ResponseBody::Full(body.as_bytes().to_vec())
+    }
+}
+
+impl fmt::Debug for ResponseBody {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ResponseBody::Full(ref bytes) => write!(f, "Full({} bytes)", bytes.len()),
+            ResponseBody::Stream(_) => write!(f, "Stream"),
+        }
+    }
+}
+
+/// An iterator over the chunks that are read from a reader
+struct ReaderChunks<R: Read> {
+    reader: R,
+    /// Set once the reader has reached the end of its input, or failed
+    done: bool,
+}
+
+impl<R: Read> Iterator for ReaderChunks<R> {
+    type Item = io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let mut chunk = vec![0; READ_CHUNK_SIZE];
+        loop {
+            match self.reader.read(&mut chunk) {
+                Ok(0) => {
+                    self.done = true;
+                    return None;
+                },
+                Ok(n) => {
+                    chunk.truncate(n);
+                    return Some(Ok(chunk));
+                },
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                },
+            }
+        }
+    }
+}
@@ -0,0 +1,28 @@
+//! The current date, for the `Date` header of responses
+
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use http::date::HttpDate;
+
+/// The most recently formatted date, and the date that it is for
+static CACHE: Mutex<Option<(HttpDate, String)>> = Mutex::new(None);
+
+/// Get the current date as an IMF-fixdate, such as `Sun, 06 Nov 1994 08:49:37 GMT`, which is the format required for
+/// the `Date` header by [RFC 7231 §7.1.1.2](https://tools.ietf.org/html/rfc7231#section-7.1.1.2).
+///
+/// The date only has a precision of one second, so it is cached and only formatted again once the second has changed.
+pub fn current_date() -> String {
+    let now = HttpDate::from(SystemTime::now());
+
+    // The cache is always left in a valid state, so it can still be used if another thread panicked while holding it
+    let mut cache = CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some((date, ref formatted)) = *cache {
+        if date == now {
+            return formatted.clone();
+        }
+    }
+    let formatted = now.to_string();
+    *cache = Some((now, formatted.clone()));
+    formatted
+}
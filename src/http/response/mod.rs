@@ -0,0 +1,647 @@
+//! HTTP responses, and writing them to a stream as described in
+//! [RFC 7230 §3](https://tools.ietf.org/html/rfc7230#section-3)
+
+mod body;
+mod date;
+
+use std::io::prelude::*;
+use std::io;
+
+use http::header::{HeaderMap, HeaderValue};
+use http::status::StatusCode;
+use http::request::{TCHAR, FIELD_CHAR};
+
+pub use self::body::ResponseBody;
+
+/// The value of the `Server` header that is sent by default
+pub const DEFAULT_SERVER: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+
+/// A response to a request, which can be written to a stream with [`write_to`](#method.write_to).
+///
+/// The framing of the body is taken care of when the response is written, so any `Transfer-Encoding` headers are
+/// replaced. The `Date`, `Server` and `Content-Length` headers are added automatically unless the response already has
+/// them, and each of them can be turned off with the [`ResponseBuilder`].
+#[derive(Debug)]
+pub struct Response {
+    /// HTTP version, which should be the one returned by `Request::get_response_version`
+    version: (u8, u8),
+    /// The status code
+    status: StatusCode,
+    /// The reason phrase, if it isn't the usual one for the status code
+    reason: Option<String>,
+    /// The response headers
+    headers: HeaderMap,
+    /// The response body
+    body: ResponseBody,
+    /// Fields that are sent after the body, which is only possible with the chunked transfer coding
+    trailers: HeaderMap,
+    /// Whether to add the `Date` header, if there isn't one already
+    auto_date: bool,
+    /// Whether to add the `Server` header, if there isn't one already
+    auto_server: bool,
+    /// Whether to add the `Content-Length` header for bodies that are in memory, if there isn't one already
+    auto_content_length: bool,
+}
+
+/// How the end of the body is indicated to the client, as described in
+/// [RFC 7230 §3.3.3](https://tools.ietf.org/html/rfc7230#section-3.3.3)
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+enum Framing {
+    /// There is no body
+    None,
+    /// The body is this many bytes long, which is given by `Content-Length`
+    Length(u64),
+    /// The body uses the chunked transfer coding
+    Chunked,
+    /// The body ends when the connection is closed
+    Close,
+}
+
+impl Response {
+    /// Create a new response with the given status, no headers and an empty body
+    pub fn new(status: StatusCode) -> Response {
+        Response {
+            version: (1, 1),
+            status,
+            reason: None,
+            headers: HeaderMap::new(),
+            body: ResponseBody::empty(),
+            trailers: HeaderMap::new(),
+            auto_date: true,
+            auto_server: true,
+            auto_content_length: true,
+        }
+    }
+
+    /// Create a builder for a response, which starts out as `200 OK`
+    pub fn builder() -> ResponseBuilder {
+        ResponseBuilder::new()
+    }
+
+    /// Get the HTTP version of the response, in the format (major, minor)
+    pub fn get_version(&self) -> (u8, u8) {
+        self.version
+    }
+    /// Get the status code
+    pub fn get_status(&self) -> StatusCode {
+        self.status
+    }
+    /// Get the reason phrase, which is the registered one for the status code unless a different one was set. This is
+    /// empty for unregistered status codes.
+    pub fn get_reason(&self) -> &str {
+        match self.reason {
+            Some(ref reason) => reason,
+            None => self.status.get_reason().unwrap_or(""),
+        }
+    }
+    /// Get the response headers
+    pub fn get_headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+    /// Get the response headers, so that they can be changed
+    pub fn get_headers_mut(&mut self) -> &mut HeaderMap {
+        &mut self.headers
+    }
+    /// Get the response body
+    pub fn get_body(&self) -> &ResponseBody {
+        &self.body
+    }
+    /// Get the trailer fields, which are sent after the body
+    pub fn get_trailers(&self) -> &HeaderMap {
+        &self.trailers
+    }
+    /// Get the trailer fields, so that they can be changed
+    pub fn get_trailers_mut(&mut self) -> &mut HeaderMap {
+        &mut self.trailers
+    }
+
+    /// Returns `true` if a response with this status code can have a body. Informational (1xx), `204 No Content` and
+    /// `304 Not Modified` responses never do, as described in
+    /// [RFC 7230 §3.3](https://tools.ietf.org/html/rfc7230#section-3.3).
+    pub fn allows_body(&self) -> bool {
+        !self.status.is_informational() && self.status != StatusCode::NO_CONTENT
+            && self.status != StatusCode::NOT_MODIFIED
+    }
+
+    /// Returns `true` if the connection has to be closed after this response, because the end of the body is marked by
+    /// closing the connection. This happens when a streamed body is sent to an HTTP/1.0 client, which doesn't
+    /// understand the chunked transfer coding.
+    pub fn requires_close(&self) -> bool {
+        matches!(self.get_framing(), Ok(Framing::Close))
+    }
+
+    /// Determine how the end of the body will be indicated.
+    ///
+    /// A `Content-Length` header that was set by the handler is used as it is, which allows a streamed body of a known
+    /// length to be sent without chunked framing. Otherwise, trailers can only be sent with the chunked transfer
+    /// coding, so it is used for HTTP/1.1 responses that have trailers even if the body is in memory.
+    fn get_framing(&self) -> io::Result<Framing> {
+        if !self.allows_body() {
+            return Ok(Framing::None);
+        }
+
+        let chunked = self.version >= (1, 1);
+        if let Some(value) = self.headers.get("Content-Length") {
+            let length = match value.to_str().and_then(|v| v.parse::<u64>().ok()) {
+                Some(length) => length,
+                None => return Err(invalid_input(format!("Invalid Content-Length: {:?}", value))),
+            };
+            if self.body.as_bytes().is_some_and(|bytes| bytes.len() as u64 != length) {
+                return Err(invalid_input(format!("Content-Length {} doesn't match the body", length)));
+            }
+            if chunked && !self.trailers.is_empty() {
+                return Err(invalid_input("Trailers can't be sent with Content-Length".to_string()));
+            }
+            return Ok(Framing::Length(length));
+        }
+
+        Ok(match self.body {
+            ResponseBody::Full(_) if chunked && !self.trailers.is_empty() => Framing::Chunked,
+            ResponseBody::Full(ref bytes) if self.auto_content_length => Framing::Length(bytes.len() as u64),
+            _ if chunked => Framing::Chunked,
+            _ => Framing::Close,
+        })
+    }
+
+    /// Write the response to `writer`. The head and the body (if it is in memory) are written with a single call to
+    /// `write_all`, so that they aren't split across more packets than they need to be. Streamed bodies are written
+    /// one chunk at a time, and `writer` is flushed after each one.
+    ///
+    /// This fails with [`io::ErrorKind::InvalidInput`] without writing anything if the response can't be written
+    /// correctly, such as if a header value contains a line break. If a streamed body fails, the response is left
+    /// incomplete and the error is returned, so the connection must be closed.
+    pub fn write_to<W: Write + ?Sized>(self, writer: &mut W) -> io::Result<()> {
+        let framing = self.get_framing()?;
+        let mut result = self.write_head(framing)?;
+        let mut end = Vec::new();
+        if framing == Framing::Chunked {
+            end.extend_from_slice(b"0\r\n");
+            for (name, value) in &self.trailers {
+                if is_framing_field(name) || name.eq_ignore_ascii_case("Trailer") {
+                    return Err(invalid_input(format!("{} can't be sent as a trailer", name)));
+                }
+                write_field(&mut end, name, value)?;
+            }
+            end.extend_from_slice(b"\r\n");
+        }
+
+        match (framing, self.body) {
+            (Framing::None, _) => writer.write_all(&result),
+            (Framing::Length(_), ResponseBody::Full(bytes)) => {
+                result.extend_from_slice(&bytes);
+                writer.write_all(&result)
+            },
+            (Framing::Chunked, ResponseBody::Full(bytes)) => {
+                write_chunk(&mut result, &bytes);
+                result.extend_from_slice(&end);
+                writer.write_all(&result)
+            },
+            (Framing::Chunked, ResponseBody::Stream(chunks)) => {
+                writer.write_all(&result)?;
+                for chunk in chunks {
+                    let mut buffer = Vec::new();
+                    write_chunk(&mut buffer, &chunk?);
+                    writer.write_all(&buffer)?;
+                    writer.flush()?;
+                }
+                writer.write_all(&end)
+            },
+            (Framing::Length(length), ResponseBody::Stream(chunks)) => {
+                writer.write_all(&result)?;
+                let mut remaining = length;
+                for chunk in chunks {
+                    let chunk = chunk?;
+                    if chunk.len() as u64 > remaining {
+                        return Err(io::Error::new(io::ErrorKind::InvalidData, "Body longer than Content-Length"));
+                    }
+                    remaining -= chunk.len() as u64;
+                    writer.write_all(&chunk)?;
+                    writer.flush()?;
+                }
+                if remaining > 0 {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, "Body shorter than Content-Length"));
+                }
+                Ok(())
+            },
+            (Framing::Close, body) => {
+                writer.write_all(&result)?;
+                match body {
+                    ResponseBody::Full(bytes) => writer.write_all(&bytes)?,
+                    ResponseBody::Stream(chunks) => for chunk in chunks {
+                        writer.write_all(&chunk?)?;
+                        writer.flush()?;
+                    },
+                }
+                Ok(())
+            },
+        }?;
+        writer.flush()
+    }
+
+    /// Write only the status line and headers to `writer`, which is how a response to a `HEAD` request is sent. The
+    /// headers are the same as the ones [`write_to`](#method.write_to) would send, including the `Content-Length` of
+    /// a body that is in memory, but neither the body nor the end of a chunked body is written.
+    ///
+    /// This fails with [`io::ErrorKind::InvalidInput`] without writing anything in the same cases as `write_to`.
+    pub fn write_head_to<W: Write + ?Sized>(self, writer: &mut W) -> io::Result<()> {
+        let framing = self.get_framing()?;
+        writer.write_all(&self.write_head(framing)?)?;
+        writer.flush()
+    }
+
+    /// Serialize the status line and headers, including the ones that are needed for `framing`
+    fn write_head(&self, framing: Framing) -> io::Result<Vec<u8>> {
+        let reason = self.get_reason();
+        if !reason.bytes().all(|b| FIELD_CHAR[b as usize]) {
+            return Err(invalid_input(format!("Invalid reason phrase: {:?}", reason)));
+        }
+
+        let mut result = format!("HTTP/{}.{} {} {}\r\n", self.version.0, self.version.1, self.status.as_u16(), reason)
+            .into_bytes();
+        if self.auto_date && !self.headers.contains("Date") {
+            write_field(&mut result, "Date", &date::current_date().into())?;
+        }
+        if self.auto_server && !self.headers.contains("Server") {
+            write_field(&mut result, "Server", &DEFAULT_SERVER.into())?;
+        }
+        for (name, value) in &self.headers {
+            let replaced = match framing {
+                Framing::Close => name.eq_ignore_ascii_case("Connection"),
+                Framing::Chunked => name.eq_ignore_ascii_case("Trailer"),
+                _ => false,
+            };
+            if is_framing_field(name) || replaced {
+                continue;
+            }
+            write_field(&mut result, name, value)?;
+        }
+
+        match framing {
+            Framing::None => (),
+            Framing::Length(length) => write_field(&mut result, "Content-Length", &length.to_string().into())?,
+            Framing::Chunked => {
+                write_field(&mut result, "Transfer-Encoding", &"chunked".into())?;
+                // Announce the trailers, as recommended by RFC 7230 §4.4
+                if !self.trailers.is_empty() {
+                    let names: Vec<&str> = self.trailers.iter().map(|(name, _)| name).collect();
+                    write_field(&mut result, "Trailer", &names.join(", ").into())?;
+                }
+            },
+            Framing::Close => write_field(&mut result, "Connection", &"close".into())?,
+        }
+        result.extend_from_slice(b"\r\n");
+
+        Ok(result)
+    }
+}
+
+/// Returns `true` for the fields that determine the framing of the body, which are always written by [`Response`]
+fn is_framing_field(name: &str) -> bool {
+    name.eq_ignore_ascii_case("Content-Length") || name.eq_ignore_ascii_case("Transfer-Encoding")
+}
+
+/// Write a chunk of a body that uses the chunked transfer coding. Empty chunks are skipped, because a chunk with size
+/// 0 marks the end of the body.
+fn write_chunk(result: &mut Vec<u8>, chunk: &[u8]) {
+    if !chunk.is_empty() {
+        result.extend_from_slice(format!("{:X}\r\n", chunk.len()).as_bytes());
+        result.extend_from_slice(chunk);
+        result.extend_from_slice(b"\r\n");
+    }
+}
+
+/// Write a header field, after checking that it can't be mistaken for anything else
+fn write_field(result: &mut Vec<u8>, name: &str, value: &HeaderValue) -> io::Result<()> {
+    if name.is_empty() || !name.bytes().all(|b| TCHAR[b as usize]) {
+        return Err(invalid_input(format!("Invalid header name: {:?}", name)));
+    }
+    // Values containing CR or LF would allow the response to be split
+    if !value.as_bytes().iter().all(|&b| FIELD_CHAR[b as usize]) {
+        return Err(invalid_input(format!("Invalid value for header {}: {:?}", name, value)));
+    }
+
+    result.extend_from_slice(name.as_bytes());
+    result.extend_from_slice(b": ");
+    result.extend_from_slice(value.as_bytes());
+    result.extend_from_slice(b"\r\n");
+    Ok(())
+}
+
+fn invalid_input(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+/// A builder for a [`Response`]
+///
+/// # Examples
+/// ```
+/// use webserver::http::response::Response;
+/// use webserver::http::status::StatusCode;
+///
+/// let response = Response::builder()
+///     .status(StatusCode::NOT_FOUND)
+///     .header("Content-Type", "text/plain")
+///     .body("Not here")
+///     .build();
+/// assert_eq!(response.get_reason(), "Not Found");
+/// ```
+#[derive(Debug)]
+pub struct ResponseBuilder {
+    response: Response,
+}
+
+impl ResponseBuilder {
+    /// Create a new builder for a `200 OK` response
+    pub fn new() -> ResponseBuilder {
+        ResponseBuilder {
+            response: Response::new(StatusCode::OK),
+        }
+    }
+
+    /// Set the HTTP version of the response
+    pub fn version(mut self, major: u8, minor: u8) -> ResponseBuilder {
+        self.response.version = (major, minor);
+        self
+    }
+
+    /// Set the status code
+    pub fn status(mut self, status: StatusCode) -> ResponseBuilder {
+        self.response.status = status;
+        self
+    }
+
+    /// Set a reason phrase to use instead of the usual one for the status code
+    pub fn reason<S: Into<String>>(mut self, reason: S) -> ResponseBuilder {
+        self.response.reason = Some(reason.into());
+        self
+    }
+
+    /// Add a header. If there are already headers with the same name, this value is stored after them.
+    pub fn header<N, V>(mut self, name: N, value: V) -> ResponseBuilder
+        where N: Into<String>, V: Into<HeaderValue> {
+        self.response.headers.add(name, value);
+        self
+    }
+
+    /// Set the body, which can be in memory or streamed (see [`ResponseBody`])
+    pub fn body<B: Into<ResponseBody>>(mut self, body: B) -> ResponseBuilder {
+        self.response.body = body.into();
+        self
+    }
+
+    /// Set whether the `Date` header is added automatically, with the time that the response is written. It isn't
+    /// added if the response already has one.
+    pub fn auto_date(mut self, enabled: bool) -> ResponseBuilder {
+        self.response.auto_date = enabled;
+        self
+    }
+
+    /// Set whether the `Server` header is added automatically, with the value [`DEFAULT_SERVER`]. It isn't added if
+    /// the response already has one.
+    pub fn auto_server(mut self, enabled: bool) -> ResponseBuilder {
+        self.response.auto_server = enabled;
+        self
+    }
+
+    /// Set whether the `Content-Length` header is added automatically for a body that is in memory. If it isn't, the
+    /// body is sent in the same way as a streamed body. A `Content-Length` header that is set explicitly is always
+    /// used, and must match the length of the body.
+    pub fn auto_content_length(mut self, enabled: bool) -> ResponseBuilder {
+        self.response.auto_content_length = enabled;
+        self
+    }
+
+    /// Add a trailer field, which is sent after the body. Trailers can only be sent to HTTP/1.1 clients, so they are
+    /// dropped if the version is HTTP/1.0.
+    pub fn trailer<N, V>(mut self, name: N, value: V) -> ResponseBuilder
+        where N: Into<String>, V: Into<HeaderValue> {
+        self.response.trailers.add(name, value);
+        self
+    }
+
+    /// Finish building the response
+    pub fn build(self) -> Response {
+        self.response
+    }
+}
+
+impl Default for ResponseBuilder {
+    fn default() -> ResponseBuilder {
+        ResponseBuilder::new()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+    use std::thread;
+
+    #[test]
+    fn test_serialize() {
+        let response = builder()
+            .version(1, 0)
+            .status(StatusCode::NOT_FOUND)
+            .header("Content-Type", "text/html")
+            .body("<h1>Not Found</h1>")
+            .build();
+
+        assert_eq!(to_bytes(response).unwrap(), &b"HTTP/1.0 404 Not Found\r\n\
+            Content-Type: text/html\r\n\
+            Content-Length: 18\r\n\
+            \r\n\
+            <h1>Not Found</h1>"[..]);
+    }
+
+    #[test]
+    fn test_automatic_headers() {
+        let response = Response::builder().body("hello").build();
+        let written = String::from_utf8(to_bytes(response).unwrap()).unwrap();
+        let lines: Vec<&str> = written.split("\r\n").collect();
+        assert_eq!(lines[0], "HTTP/1.1 200 OK");
+        assert!(lines[1].starts_with("Date: ") && lines[1].ends_with(" GMT"), "{:?}", lines[1]);
+        assert_eq!(&lines[2..], &[&format!("Server: {}", DEFAULT_SERVER)[..], "Content-Length: 5", "", "hello"]);
+
+        // Each of them can be overridden
+        let response = Response::builder()
+            .header("Date", "Sun, 06 Nov 1994 08:49:37 GMT")
+            .header("Server", "test")
+            .body("hello")
+            .build();
+        assert_eq!(to_bytes(response).unwrap(), &b"HTTP/1.1 200 OK\r\n\
+            Date: Sun, 06 Nov 1994 08:49:37 GMT\r\n\
+            Server: test\r\n\
+            Content-Length: 5\r\n\
+            \r\n\
+            hello"[..]);
+
+        // Or turned off, in which case the body is sent like a streamed one
+        let response = Response::builder()
+            .auto_date(false)
+            .auto_server(false)
+            .auto_content_length(false)
+            .body("hello")
+            .build();
+        assert_eq!(to_bytes(response).unwrap(), &b"HTTP/1.1 200 OK\r\n\
+            Transfer-Encoding: chunked\r\n\
+            \r\n\
+            5\r\nhello\r\n0\r\n\r\n"[..]);
+    }
+
+    #[test]
+    fn test_content_length() {
+        // A streamed body can be sent with a known length
+        let response = builder()
+            .header("Content-Length", "11")
+            .body(ResponseBody::from_chunks(vec!["hello", " world"]))
+            .build();
+        assert_eq!(to_bytes(response).unwrap(), &b"HTTP/1.1 200 OK\r\nContent-Length: 11\r\n\r\nhello world"[..]);
+
+        for &length in &["10", "12"] {
+            let response = builder()
+                .header("Content-Length", length)
+                .body(ResponseBody::from_chunks(vec!["hello", " world"]))
+                .build();
+            assert_eq!(to_bytes(response).unwrap_err().kind(), io::ErrorKind::InvalidData);
+        }
+
+        for &length in &["10", "-11", "eleven"] {
+            let response = builder().header("Content-Length", length).body("hello world").build();
+            assert_eq!(to_bytes(response).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+        }
+    }
+
+    #[test]
+    fn test_no_body() {
+        let response = builder()
+            .status(StatusCode::NOT_MODIFIED)
+            .reason("Still Not Modified")
+            .body("ignored")
+            .build();
+        assert!(!response.allows_body());
+        assert_eq!(to_bytes(response).unwrap(), b"HTTP/1.1 304 Still Not Modified\r\n\r\n");
+    }
+
+    #[test]
+    fn test_chunked() {
+        let response = builder()
+            .body(ResponseBody::from_chunks(vec!["hello", "", " world, and more"]))
+            .trailer("Checksum", "abc")
+            .build();
+        assert!(!response.requires_close());
+
+        assert_eq!(to_bytes(response).unwrap(), &b"HTTP/1.1 200 OK\r\n\
+            Transfer-Encoding: chunked\r\n\
+            Trailer: Checksum\r\n\
+            \r\n\
+            5\r\nhello\r\n\
+            10\r\n world, and more\r\n\
+            0\r\n\
+            Checksum: abc\r\n\
+            \r\n"[..]);
+
+        // Trailers can be sent after a body in memory too
+        let response = builder().body("hello").trailer("Checksum", "abc").build();
+        assert_eq!(to_bytes(response).unwrap(), &b"HTTP/1.1 200 OK\r\n\
+            Transfer-Encoding: chunked\r\n\
+            Trailer: Checksum\r\n\
+            \r\n\
+            5\r\nhello\r\n\
+            0\r\n\
+            Checksum: abc\r\n\
+            \r\n"[..]);
+    }
+
+    #[test]
+    fn test_close_delimited() {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            for line in &["first\n", "second\n"] {
+                sender.send(*line).unwrap();
+            }
+        });
+        let response = builder()
+            .version(1, 0)
+            .header("Connection", "keep-alive")
+            .body(ResponseBody::from_channel(receiver))
+            .trailer("Checksum", "abc")
+            .build();
+        assert!(response.requires_close());
+
+        assert_eq!(to_bytes(response).unwrap(), &b"HTTP/1.0 200 OK\r\nConnection: close\r\n\r\nfirst\nsecond\n"[..]);
+    }
+
+    #[test]
+    fn test_head_only() {
+        // The headers are the ones that would be sent with the body
+        let response = builder().header("Content-Type", "text/plain").body("hello").build();
+        let mut written = Vec::new();
+        response.write_head_to(&mut written).unwrap();
+        assert_eq!(written, &b"HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: 5\r\n\r\n"[..]);
+
+        // A streamed body isn't read, and the chunked body isn't terminated
+        let response = builder()
+            .body(ResponseBody::from_reader(FailingReader(0)))
+            .trailer("Checksum", "abc")
+            .build();
+        let mut written = Vec::new();
+        response.write_head_to(&mut written).unwrap();
+        assert_eq!(written, &b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\nTrailer: Checksum\r\n\r\n"[..]);
+
+        let response = builder().header("Content-Length", "6").body("hello").build();
+        assert_eq!(response.write_head_to(&mut Vec::new()).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_stream_error() {
+        let response = builder().body(ResponseBody::from_reader(FailingReader(1))).build();
+        let mut written = Vec::new();
+        assert_eq!(response.write_to(&mut written).unwrap_err().kind(), io::ErrorKind::BrokenPipe);
+
+        // The response isn't terminated, so the client can tell that it is incomplete
+        assert!(written.ends_with(b"\r\n\r\n1\r\na\r\n"), "{:?}", String::from_utf8_lossy(&written));
+    }
+
+    #[test]
+    fn test_invalid() {
+        let responses = vec![
+            builder().header("Location", "/a\r\nSet-Cookie: a=b").build(),
+            builder().header("Bad Name", "value").build(),
+            builder().header("", "value").build(),
+            builder().reason("OK\r\n").build(),
+            builder().trailer("Content-Length", "5").build(),
+        ];
+        for response in responses {
+            let mut written = Vec::new();
+            let err = response.write_to(&mut written).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+            assert!(written.is_empty());
+        }
+    }
+
+    /// A response builder that doesn't add the `Date` or `Server` headers, so that the output is predictable
+    fn builder() -> ResponseBuilder {
+        Response::builder().auto_date(false).auto_server(false)
+    }
+
+    fn to_bytes(response: Response) -> io::Result<Vec<u8>> {
+        let mut written = Vec::new();
+        response.write_to(&mut written)?;
+        Ok(written)
+    }
+
+    /// A reader that returns `a` this many times, and then fails
+    struct FailingReader(usize);
+
+    impl Read for FailingReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.0 == 0 {
+                return Err(io::Error::new(io::ErrorKind::BrokenPipe, "Failed"));
+            }
+            self.0 -= 1;
+            buf[0] = b'a';
+            Ok(1)
+        }
+    }
+}
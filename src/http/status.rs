@@ -0,0 +1,256 @@
+//! Response status codes, as defined in [RFC 7231 §6](https://tools.ietf.org/html/rfc7231#section-6) and registered
+//! in the [IANA HTTP Status Code Registry](https://www.iana.org/assignments/http-status-codes/http-status-codes.xhtml)
+
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+
+/// A response status code, which is always a three-digit number between 100 and 599.
+///
+/// Any code in that range can be used, not just the registered ones, because clients have to understand unrecognised
+/// codes as equivalent to the `x00` code of their class. The registered codes are available as constants, such as
+/// [`StatusCode::NOT_FOUND`].
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct StatusCode(u16);
+
+impl StatusCode {
+    /// Create a status code from a number, which must be between 100 and 599 (inclusive)
+    pub fn from_u16(code: u16) -> Result<StatusCode, InvalidStatusCode> {
+        if (100..600).contains(&code) {
+            Ok(StatusCode(code))
+        } else {
+            Err(InvalidStatusCode(code))
+        }
+    }
+
+    /// Get the status code as a number
+    pub fn as_u16(self) -> u16 {
+        self.0
+    }
+
+    /// Get the reason phrase that was registered with the status code, or `None` if the code isn't registered
+    pub fn get_reason(self) -> Option<&'static str> {
+        canonical_reason(self.0)
+    }
+
+    /// Returns `true` for 1xx (Informational) codes, which are interim responses sent before the final one
+    pub fn is_informational(self) -> bool {
+        self.0 < 200
+    }
+    /// Returns `true` for 2xx (Successful) codes
+    pub fn is_success(self) -> bool {
+        (200..300).contains(&self.0)
+    }
+    /// Returns `true` for 3xx (Redirection) codes
+    pub fn is_redirection(self) -> bool {
+        (300..400).contains(&self.0)
+    }
+    /// Returns `true` for 4xx (Client Error) codes
+    pub fn is_client_error(self) -> bool {
+        (400..500).contains(&self.0)
+    }
+    /// Returns `true` for 5xx (Server Error) codes
+    pub fn is_server_error(self) -> bool {
+        self.0 >= 500
+    }
+}
+
+/// Status codes are displayed with their reason phrase if they have one, such as `404 Not Found`
+impl fmt::Display for StatusCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.get_reason() {
+            Some(reason) => write!(f, "{} {}", self.0, reason),
+            None => write!(f, "{}", self.0),
+        }
+    }
+}
+
+impl TryFrom<u16> for StatusCode {
+    type Error = InvalidStatusCode;
+
+    fn try_from(code: u16) -> Result<StatusCode, InvalidStatusCode> {
+        StatusCode::from_u16(code)
+    }
+}
+
+impl From<StatusCode> for u16 {
+    fn from(status: StatusCode) -> u16 {
+        status.0
+    }
+}
+
+impl PartialEq<u16> for StatusCode {
+    fn eq(&self, other: &u16) -> bool {
+        self.0 == *other
+    }
+}
+
+/// The error returned when trying to create a [`StatusCode`] outside of the range 100-599
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct InvalidStatusCode(u16);
+
+impl fmt::Display for InvalidStatusCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid status code {}, which must be between 100 and 599", self.0)
+    }
+}
+
+impl Error for InvalidStatusCode {}
+
+/// Define a constant for each registered status code, and the function that looks up their reason phrases
+macro_rules! status_codes {
+    ($(($code:literal, $name:ident, $reason:expr, $spec:expr, $link:expr);)+) => {
+        impl StatusCode {
+            $(
+                #[doc = concat!("`", stringify!($code), " ", $reason, "`, defined in [", $spec, "](", $link, ")")]
+                pub const $name: StatusCode = StatusCode($code);
+            )+
+        }
+
+        /// Get the registered reason phrase for a status code
+        fn canonical_reason(code: u16) -> Option<&'static str> {
+            match code {
+                $($code => Some($reason),)+
+                _ => None,
+            }
+        }
+    }
+}
+
+status_codes! {
+    (100, CONTINUE, "Continue", "RFC 7231 §6.2.1", "https://tools.ietf.org/html/rfc7231#section-6.2.1");
+    (101, SWITCHING_PROTOCOLS, "Switching Protocols", "RFC 7231 §6.2.2",
+        "https://tools.ietf.org/html/rfc7231#section-6.2.2");
+    (102, PROCESSING, "Processing", "RFC 2518 §10.1", "https://tools.ietf.org/html/rfc2518#section-10.1");
+    (103, EARLY_HINTS, "Early Hints", "RFC 8297", "https://tools.ietf.org/html/rfc8297");
+
+    (200, OK, "OK", "RFC 7231 §6.3.1", "https://tools.ietf.org/html/rfc7231#section-6.3.1");
+    (201, CREATED, "Created", "RFC 7231 §6.3.2", "https://tools.ietf.org/html/rfc7231#section-6.3.2");
+    (202, ACCEPTED, "Accepted", "RFC 7231 §6.3.3", "https://tools.ietf.org/html/rfc7231#section-6.3.3");
+    (203, NON_AUTHORITATIVE_INFORMATION, "Non-Authoritative Information", "RFC 7231 §6.3.4",
+        "https://tools.ietf.org/html/rfc7231#section-6.3.4");
+    (204, NO_CONTENT, "No Content", "RFC 7231 §6.3.5", "https://tools.ietf.org/html/rfc7231#section-6.3.5");
+    (205, RESET_CONTENT, "Reset Content", "RFC 7231 §6.3.6", "https://tools.ietf.org/html/rfc7231#section-6.3.6");
+    (206, PARTIAL_CONTENT, "Partial Content", "RFC 7233 §4.1", "https://tools.ietf.org/html/rfc7233#section-4.1");
+    (207, MULTI_STATUS, "Multi-Status", "RFC 4918 §11.1", "https://tools.ietf.org/html/rfc4918#section-11.1");
+    (208, ALREADY_REPORTED, "Already Reported", "RFC 5842 §7.1", "https://tools.ietf.org/html/rfc5842#section-7.1");
+    (226, IM_USED, "IM Used", "RFC 3229 §10.4.1", "https://tools.ietf.org/html/rfc3229#section-10.4.1");
+
+    (300, MULTIPLE_CHOICES, "Multiple Choices", "RFC 7231 §6.4.1",
+        "https://tools.ietf.org/html/rfc7231#section-6.4.1");
+    (301, MOVED_PERMANENTLY, "Moved Permanently", "RFC 7231 §6.4.2",
+        "https://tools.ietf.org/html/rfc7231#section-6.4.2");
+    (302, FOUND, "Found", "RFC 7231 §6.4.3", "https://tools.ietf.org/html/rfc7231#section-6.4.3");
+    (303, SEE_OTHER, "See Other", "RFC 7231 §6.4.4", "https://tools.ietf.org/html/rfc7231#section-6.4.4");
+    (304, NOT_MODIFIED, "Not Modified", "RFC 7232 §4.1", "https://tools.ietf.org/html/rfc7232#section-4.1");
+    (305, USE_PROXY, "Use Proxy", "RFC 7231 §6.4.5", "https://tools.ietf.org/html/rfc7231#section-6.4.5");
+    (307, TEMPORARY_REDIRECT, "Temporary Redirect", "RFC 7231 §6.4.7",
+        "https://tools.ietf.org/html/rfc7231#section-6.4.7");
+    (308, PERMANENT_REDIRECT, "Permanent Redirect", "RFC 7538 §3", "https://tools.ietf.org/html/rfc7538#section-3");
+
+    (400, BAD_REQUEST, "Bad Request", "RFC 7231 §6.5.1", "https://tools.ietf.org/html/rfc7231#section-6.5.1");
+    (401, UNAUTHORIZED, "Unauthorized", "RFC 7235 §3.1", "https://tools.ietf.org/html/rfc7235#section-3.1");
+    (402, PAYMENT_REQUIRED, "Payment Required", "RFC 7231 §6.5.2",
+        "https://tools.ietf.org/html/rfc7231#section-6.5.2");
+    (403, FORBIDDEN, "Forbidden", "RFC 7231 §6.5.3", "https://tools.ietf.org/html/rfc7231#section-6.5.3");
+    (404, NOT_FOUND, "Not Found", "RFC 7231 §6.5.4", "https://tools.ietf.org/html/rfc7231#section-6.5.4");
+    (405, METHOD_NOT_ALLOWED, "Method Not Allowed", "RFC 7231 §6.5.5",
+        "https://tools.ietf.org/html/rfc7231#section-6.5.5");
+    (406, NOT_ACCEPTABLE, "Not Acceptable", "RFC 7231 §6.5.6", "https://tools.ietf.org/html/rfc7231#section-6.5.6");
+    (407, PROXY_AUTHENTICATION_REQUIRED, "Proxy Authentication Required", "RFC 7235 §3.2",
+        "https://tools.ietf.org/html/rfc7235#section-3.2");
+    (408, REQUEST_TIMEOUT, "Request Timeout", "RFC 7231 §6.5.7", "https://tools.ietf.org/html/rfc7231#section-6.5.7");
+    (409, CONFLICT, "Conflict", "RFC 7231 §6.5.8", "https://tools.ietf.org/html/rfc7231#section-6.5.8");
+    (410, GONE, "Gone", "RFC 7231 §6.5.9", "https://tools.ietf.org/html/rfc7231#section-6.5.9");
+    (411, LENGTH_REQUIRED, "Length Required", "RFC 7231 §6.5.10",
+        "https://tools.ietf.org/html/rfc7231#section-6.5.10");
+    (412, PRECONDITION_FAILED, "Precondition Failed", "RFC 7232 §4.2",
+        "https://tools.ietf.org/html/rfc7232#section-4.2");
+    (413, PAYLOAD_TOO_LARGE, "Payload Too Large", "RFC 7231 §6.5.11",
+        "https://tools.ietf.org/html/rfc7231#section-6.5.11");
+    (414, URI_TOO_LONG, "URI Too Long", "RFC 7231 §6.5.12", "https://tools.ietf.org/html/rfc7231#section-6.5.12");
+    (415, UNSUPPORTED_MEDIA_TYPE, "Unsupported Media Type", "RFC 7231 §6.5.13",
+        "https://tools.ietf.org/html/rfc7231#section-6.5.13");
+    (416, RANGE_NOT_SATISFIABLE, "Range Not Satisfiable", "RFC 7233 §4.4",
+        "https://tools.ietf.org/html/rfc7233#section-4.4");
+    (417, EXPECTATION_FAILED, "Expectation Failed", "RFC 7231 §6.5.14",
+        "https://tools.ietf.org/html/rfc7231#section-6.5.14");
+    (421, MISDIRECTED_REQUEST, "Misdirected Request", "RFC 7540 §9.1.2",
+        "https://tools.ietf.org/html/rfc7540#section-9.1.2");
+    (422, UNPROCESSABLE_ENTITY, "Unprocessable Entity", "RFC 4918 §11.2",
+        "https://tools.ietf.org/html/rfc4918#section-11.2");
+    (423, LOCKED, "Locked", "RFC 4918 §11.3", "https://tools.ietf.org/html/rfc4918#section-11.3");
+    (424, FAILED_DEPENDENCY, "Failed Dependency", "RFC 4918 §11.4", "https://tools.ietf.org/html/rfc4918#section-11.4");
+    (425, TOO_EARLY, "Too Early", "RFC 8470 §5.2", "https://tools.ietf.org/html/rfc8470#section-5.2");
+    (426, UPGRADE_REQUIRED, "Upgrade Required", "RFC 7231 §6.5.15",
+        "https://tools.ietf.org/html/rfc7231#section-6.5.15");
+    (428, PRECONDITION_REQUIRED, "Precondition Required", "RFC 6585 §3", "https://tools.ietf.org/html/rfc6585#section-3");
+    (429, TOO_MANY_REQUESTS, "Too Many Requests", "RFC 6585 §4", "https://tools.ietf.org/html/rfc6585#section-4");
+    (431, REQUEST_HEADER_FIELDS_TOO_LARGE, "Request Header Fields Too Large", "RFC 6585 §5",
+        "https://tools.ietf.org/html/rfc6585#section-5");
+    (451, UNAVAILABLE_FOR_LEGAL_REASONS, "Unavailable For Legal Reasons", "RFC 7725 §3",
+        "https://tools.ietf.org/html/rfc7725#section-3");
+
+    (500, INTERNAL_SERVER_ERROR, "Internal Server Error", "RFC 7231 §6.6.1",
+        "https://tools.ietf.org/html/rfc7231#section-6.6.1");
+    (501, NOT_IMPLEMENTED, "Not Implemented", "RFC 7231 §6.6.2", "https://tools.ietf.org/html/rfc7231#section-6.6.2");
+    (502, BAD_GATEWAY, "Bad Gateway", "RFC 7231 §6.6.3", "https://tools.ietf.org/html/rfc7231#section-6.6.3");
+    (503, SERVICE_UNAVAILABLE, "Service Unavailable", "RFC 7231 §6.6.4",
+        "https://tools.ietf.org/html/rfc7231#section-6.6.4");
+    (504, GATEWAY_TIMEOUT, "Gateway Timeout", "RFC 7231 §6.6.5", "https://tools.ietf.org/html/rfc7231#section-6.6.5");
+    (505, HTTP_VERSION_NOT_SUPPORTED, "HTTP Version Not Supported", "RFC 7231 §6.6.6",
+        "https://tools.ietf.org/html/rfc7231#section-6.6.6");
+    (506, VARIANT_ALSO_NEGOTIATES, "Variant Also Negotiates", "RFC 2295 §8.1",
+        "https://tools.ietf.org/html/rfc2295#section-8.1");
+    (507, INSUFFICIENT_STORAGE, "Insufficient Storage", "RFC 4918 §11.5",
+        "https://tools.ietf.org/html/rfc4918#section-11.5");
+    (508, LOOP_DETECTED, "Loop Detected", "RFC 5842 §7.2", "https://tools.ietf.org/html/rfc5842#section-7.2");
+    (510, NOT_EXTENDED, "Not Extended", "RFC 2774 §7", "https://tools.ietf.org/html/rfc2774#section-7");
+    (511, NETWORK_AUTHENTICATION_REQUIRED, "Network Authentication Required", "RFC 6585 §6",
+        "https://tools.ietf.org/html/rfc6585#section-6");
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validation() {
+        assert_eq!(StatusCode::from_u16(404), Ok(StatusCode::NOT_FOUND));
+        assert_eq!(StatusCode::try_from(599).map(u16::from), Ok(599));
+        for &code in &[0, 99, 600, 1000] {
+            assert_eq!(StatusCode::from_u16(code), Err(InvalidStatusCode(code)));
+        }
+    }
+
+    #[test]
+    fn test_reason() {
+        assert_eq!(StatusCode::OK.get_reason(), Some("OK"));
+        assert_eq!(StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE.get_reason(), Some("Request Header Fields Too Large"));
+        assert_eq!(StatusCode::from_u16(299).unwrap().get_reason(), None);
+        assert_eq!(StatusCode::from_u16(306).unwrap().get_reason(), None);
+
+        assert_eq!(StatusCode::NOT_FOUND.to_string(), "404 Not Found");
+        assert_eq!(StatusCode::from_u16(499).unwrap().to_string(), "499");
+    }
+
+    #[test]
+    fn test_class() {
+        let classes = [
+            (StatusCode::CONTINUE, 1), (StatusCode::EARLY_HINTS, 1), (StatusCode::OK, 2), (StatusCode::IM_USED, 2),
+            (StatusCode::MULTIPLE_CHOICES, 3), (StatusCode::PERMANENT_REDIRECT, 3), (StatusCode::BAD_REQUEST, 4),
+            (StatusCode::UNAVAILABLE_FOR_LEGAL_REASONS, 4), (StatusCode::INTERNAL_SERVER_ERROR, 5),
+            (StatusCode::from_u16(599).unwrap(), 5),
+        ];
+        for &(status, class) in &classes {
+            let actual = [
+                status.is_informational(), status.is_success(), status.is_redirection(), status.is_client_error(),
+                status.is_server_error(),
+            ];
+            let mut expected = [false; 5];
+            expected[class - 1] = true;
+            assert_eq!(actual, expected, "for {}", status);
+        }
+    }
+}
@@ -0,0 +1,3 @@
+//! A small HTTP/1.1 server, written using only the standard library
+
+pub mod http;
@@ -1,10 +1,21 @@
-pub mod http;
+extern crate webserver;
 
 use std::io::prelude::*;
 use std::net::{TcpListener, TcpStream};
-use std::error::Error;
+use std::thread;
+use std::time::Duration;
 
-use http::request::Request;
+use webserver::http::request::{Connection, Method, Request};
+use webserver::http::response::Response;
+use webserver::http::status::StatusCode;
+
+/// How long an idle connection is kept open while waiting for the next request
+const IDLE_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long each read can take once a request has started, which is longer so that slow clients can still send large
+/// bodies
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+/// The maximum number of requests that will be handled on one connection before it is closed
+const MAX_REQUESTS: usize = 100;
 
 fn main() {
     let listener = TcpListener::bind("127.0.0.1:8080").unwrap();
@@ -12,39 +23,268 @@ fn main() {
     for stream in listener.incoming() {
         let stream = stream.unwrap();
 
-        handle_connection(stream);
+        // Connections are kept open between requests, so each one needs its own thread to avoid blocking the others
+        thread::spawn(|| handle_connection(stream));
     }
 }
 
-fn handle_connection(mut stream: TcpStream) {
-    match Request::from(&mut stream) {
-        Ok(d) => process_request(&mut stream, d),
-        Err(e) => {
-            println!("{}", e.description());
-            if let Some(code) = e.http_response_code() {
-                send_error(&mut stream, code);
+fn handle_connection(stream: TcpStream) {
+    // Requests are read from their own handle to the stream, so that the response can be written while they are alive
+    let mut output = stream.try_clone().unwrap();
+    let mut connection = Connection::new(stream.try_clone().unwrap());
+    connection.set_continue_writer(stream);
+
+    serve(connection, &mut output, wait_for_request);
+}
+
+/// Wait for the client to start sending the next request, returning `false` if it closes the connection or doesn't
+/// send anything within [`IDLE_TIMEOUT`]. Reading the rest of the request is allowed to take longer.
+fn wait_for_request(stream: &mut TcpStream) -> bool {
+    let started = stream.set_read_timeout(Some(IDLE_TIMEOUT)).and_then(|_| stream.peek(&mut [0]));
+    matches!(started, Ok(n) if n > 0) && stream.set_read_timeout(Some(REQUEST_TIMEOUT)).is_ok()
+}
+
+/// Answer the requests on a connection, writing the responses to `output`, until the connection needs to be closed.
+/// Before reading each request that hasn't started to arrive yet, `wait` is called with the stream, and the connection
+/// is closed if it returns `false`.
+fn serve<R, W, F>(mut connection: Connection<R>, output: &mut W, mut wait: F)
+    where R: Read, W: Write, F: FnMut(&mut R) -> bool {
+    // Pipelined requests are answered one at a time, in the order that they were received
+    for count in 1..=MAX_REQUESTS {
+        if !connection.has_buffered() && !wait(connection.get_mut()) {
+            break;
+        }
+        match connection.next_request() {
+            Ok(Some(mut req)) => {
+                // If the client is waiting for `100 Continue` and the handler doesn't read the body, then the client
+                // may or may not send the body after the response, so the connection can't be reused
+                let keep_alive = req.is_persistent() && count < MAX_REQUESTS && !req.is_awaiting_continue();
+                let reusable = process_request(output, &req, keep_alive);
+                if !reusable || req.is_awaiting_continue() {
+                    break;
+                }
+
+                // The rest of the body has to be read before the next request, which follows it on the connection. If
+                // it can't be read then the framing is broken, so there's no way to tell where the next request starts.
+                if let Err(e) = req.get_body_reader().discard() {
+                    println!("{}", e);
+                    break;
+                }
+            },
+            // The client closed the connection
+            Ok(None) => break,
+            Err(e) => {
+                println!("{}", e);
+                if let Some(code) = e.http_response_code() {
+                    send_error(output, code);
+                }
+                break;
             }
         }
-    };
+    }
 
-    stream.flush().unwrap();
+    output.flush().unwrap();
 }
 
-fn send_error(stream: &mut TcpStream, response_code: u16) {
-    let headers = format!("HTTP/1.1 {} GENERIC ERROR", response_code);
-    let body = format!("<h1>Error</h1><p>{}</p>", response_code);
-    
-    let response = format!("{}\r\n\r\n{}", headers, body);
-    stream.write(response.as_bytes()).unwrap();
+fn send_error<W: Write>(stream: &mut W, status: StatusCode) {
+    // After an error the rest of the request can't be trusted, so the connection is always closed
+    let response = Response::builder()
+        .status(status)
+        .header("Content-Type", "text/html")
+        .header("Connection", "close")
+        .body(format!("<h1>Error</h1><p>{}</p>", status))
+        .build();
+
+    send_response(stream, response, false);
 }
 
-fn process_request(stream: &mut TcpStream, req: Request) {
-    let headers = "HTTP/1.1 200 OK";
+/// Respond to a request, returning `true` if the connection can be used for another request afterwards
+fn process_request<W: Write>(stream: &mut W, req: &Request, keep_alive: bool) -> bool {
+    let (major, minor) = req.get_response_version();
     let mut body = format!("<h1>Success</h1><p>Requested {}</p><h2>Headers</h2>", req.get_target());
     for header in req.get_headers() {
         body = format!("{}<p><b>{}</b>: {}", body, header.0, header.1);
     }
-    
-    let response = format!("{}\r\n\r\n{}", headers, body);
-    stream.write(response.as_bytes()).unwrap();
-}
\ No newline at end of file
+
+    let mut response = Response::builder()
+        .version(major, minor)
+        .header("Content-Type", "text/html")
+        .body(body)
+        .build();
+
+    // HTTP/1.1 connections are persistent by default, but HTTP/1.0 clients need to be told that the connection is
+    // being kept open
+    if !keep_alive {
+        response.get_headers_mut().set("Connection", "close");
+    } else if req.get_version() < (1, 1) {
+        response.get_headers_mut().set("Connection", "keep-alive");
+    }
+    // The response has to be sent even if the connection will be closed afterwards. A `HEAD` request gets the same
+    // headers as a `GET`, but no body.
+    let reusable = send_response(stream, response, *req.get_method() == Method::Head);
+    keep_alive && reusable
+}
+
+/// Write a response, or only its head if `head_only` is set, returning `true` if the connection can be used for
+/// another request afterwards
+fn send_response<W: Write>(stream: &mut W, response: Response, head_only: bool) -> bool {
+    let reusable = !response.requires_close();
+    let result = if head_only { response.write_head_to(stream) } else { response.write_to(stream) };
+    match result {
+        Ok(()) => reusable,
+        Err(e) => {
+            println!("Failed to send response: {}", e);
+            false
+        },
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_process_request() {
+        let mut input: &[u8] = b"GET /a HTTP/1.1\r\nHost: a\r\n\r\n";
+        let req = Request::from(&mut input).unwrap();
+
+        let mut output = Vec::new();
+        assert!(process_request(&mut output, &req, true));
+        assert!(output.starts_with(b"HTTP/1.1 200 OK\r\n"));
+        assert!(!contains(&output, b"Connection:"));
+
+        // The response is still sent when the connection is going to be closed
+        let mut output = Vec::new();
+        assert!(!process_request(&mut output, &req, false));
+        assert!(output.starts_with(b"HTTP/1.1 200 OK\r\n"));
+        assert!(contains(&output, b"\r\nConnection: close\r\n"));
+        assert!(contains(&output, b"Requested /a"));
+    }
+
+    #[test]
+    fn test_pipelined() {
+        let output = serve_input(concat!(
+            "POST /a HTTP/1.1\r\nHost: a\r\nContent-Length: 5\r\n\r\nhello",
+            "GET /b HTTP/1.1\r\nHost: a\r\n\r\n",
+            "GET /c HTTP/1.1\r\nHost: a\r\n\r\n",
+        ));
+
+        // The unread body of the first request is skipped, and the responses are in order
+        let paths: Vec<_> = output.split("HTTP/1.1 200 OK").skip(1)
+            .map(|response| response.split("Requested ").nth(1).unwrap().split('<').next().unwrap())
+            .collect();
+        assert_eq!(paths, vec!["/a", "/b", "/c"]);
+        assert!(!output.contains("Connection: close"));
+    }
+
+    #[test]
+    fn test_head() {
+        let output = serve_input(concat!(
+            "HEAD / HTTP/1.1\r\nHost: a\r\n\r\n",
+            "GET /b HTTP/1.1\r\nHost: a\r\n\r\n",
+        ));
+
+        // The response to `HEAD` says how long the body would be, but the next response follows straight after it
+        let (head, rest) = output.split_at(output.find("\r\n\r\n").unwrap() + 4);
+        assert!(head.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(rest.starts_with("HTTP/1.1 200 OK\r\n"), "{:?}", rest);
+        assert!(rest.contains("Requested /b"));
+        assert!(!head.contains("Content-Length: 0\r\n"));
+        assert!(head.contains("\r\nContent-Length: "));
+    }
+
+    #[test]
+    fn test_close() {
+        // Requests after the one that closes the connection aren't answered
+        let output = serve_input(concat!(
+            "GET /a HTTP/1.1\r\nHost: a\r\n\r\n",
+            "GET /b HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n",
+            "GET /c HTTP/1.1\r\nHost: a\r\n\r\n",
+        ));
+        assert_eq!(output.matches("HTTP/1.1 200 OK").count(), 2);
+        assert!(output.contains("Requested /b"));
+        assert!(!output.contains("Requested /c"));
+
+        // HTTP/1.0 connections are closed unless the client asks for them to be kept alive
+        let output = serve_input("GET /a HTTP/1.0\r\n\r\nGET /b HTTP/1.0\r\n\r\n");
+        assert_eq!(output.matches("HTTP/1.0 200 OK").count(), 1);
+        assert!(output.contains("Connection: close\r\n"));
+
+        let output = serve_input("GET /a HTTP/1.0\r\nConnection: keep-alive\r\n\r\nGET /b HTTP/1.0\r\n\r\n");
+        assert_eq!(output.matches("HTTP/1.0 200 OK").count(), 2);
+    }
+
+    #[test]
+    fn test_request_cap() {
+        let input = "GET / HTTP/1.1\r\nHost: a\r\n\r\n".repeat(MAX_REQUESTS + 1);
+        let output = serve_input(&input);
+
+        // The last request that is answered says that the connection will be closed
+        assert_eq!(output.matches("HTTP/1.1 200 OK").count(), MAX_REQUESTS);
+        assert_eq!(output.matches("Connection: close\r\n").count(), 1);
+        let last = output.rsplit("HTTP/1.1 200 OK").next().unwrap();
+        assert!(last.contains("Connection: close\r\n"));
+    }
+
+    #[test]
+    fn test_errors() {
+        // An invalid request gets an error response, and the connection is closed
+        let output = serve_input(concat!(
+            "GET /a HTTP/1.1\r\nHost: a\r\n\r\n",
+            "GET /b HTTP/1.1\r\n\r\n",
+            "GET /c HTTP/1.1\r\nHost: a\r\n\r\n",
+        ));
+        assert_eq!(output.matches("HTTP/1.1 200 OK").count(), 1);
+        assert!(output.contains("HTTP/1.1 400 Bad Request\r\n"));
+        assert!(!output.contains("Requested /c"));
+
+        // A request that is cut off doesn't get a response
+        assert_eq!(serve_input("GET / HTTP/1.1\r\nHo"), "");
+    }
+
+    #[test]
+    fn test_wait() {
+        // `wait` isn't called while the next request is already buffered, and the connection is closed if it returns
+        // `false`
+        let mut calls = 0;
+        let mut output = Vec::new();
+        let input: &[u8] = b"GET /a HTTP/1.1\r\nHost: a\r\n\r\nGET /b HTTP/1.1\r\nHost: a\r\n\r\n";
+        serve(Connection::new(input), &mut output, |_| {
+            calls += 1;
+            calls == 1
+        });
+        assert_eq!(calls, 2);
+        assert_eq!(String::from_utf8(output).unwrap().matches("HTTP/1.1 200 OK").count(), 2);
+
+        let mut output = Vec::new();
+        serve(Connection::new(input), &mut output, |_| false);
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn test_process_request_http_1_0() {
+        let mut input: &[u8] = b"GET / HTTP/1.0\r\n\r\n";
+        let req = Request::from(&mut input).unwrap();
+
+        let mut output = Vec::new();
+        assert!(process_request(&mut output, &req, true));
+        assert!(contains(&output, b"\r\nConnection: keep-alive\r\n"));
+
+        let mut output = Vec::new();
+        assert!(!process_request(&mut output, &req, false));
+        assert!(output.starts_with(b"HTTP/1.0 200 OK\r\n"));
+        assert!(contains(&output, b"\r\nConnection: close\r\n"));
+    }
+
+    /// Serve a connection that receives `input`, and return everything that was sent in response
+    fn serve_input(input: &str) -> String {
+        let mut output = Vec::new();
+        serve(Connection::new(input.as_bytes()), &mut output, |_| true);
+        String::from_utf8(output).unwrap()
+    }
+
+    fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+        haystack.windows(needle.len()).any(|w| w == needle)
+    }
+}